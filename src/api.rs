@@ -1,78 +1,146 @@
 use crate::api_client::ApiClient;
 use crate::config::AdapterConfig;
+use crate::providers::{ChatMessage, EventStream, StreamEvent, Usage};
 use futures::StreamExt;
 use log::error;
+use std::io::Write;
 
-pub async fn process_response_stream(adapter_config: &AdapterConfig, prompt: &str) {
-    match adapter_config {
-        AdapterConfig::OpenAI(config) => {
-            process_openai_request(ApiClient::new(AdapterConfig::OpenAI(config.clone())), prompt).await;
-        },
-        AdapterConfig::Claude(config) => {
-            process_claude_request(ApiClient::new(AdapterConfig::Claude(config.clone())), prompt).await;
-        },
-        AdapterConfig::Zhipu(config) => {
-            process_zhipu_request(ApiClient::new(AdapterConfig::Zhipu(config.clone())), prompt).await;
-        },
-    }
+pub const DEFAULT_SYSTEM_PROMPT: &str = "You are a helpful assistant.";
+
+const DIM: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+const CLEAR_LINE: &str = "\r\x1b[2K";
+
+#[derive(Debug, Clone, Default)]
+pub struct RenderOptions {
+    /// Print the model's reasoning (dimmed) instead of hiding it.
+    pub show_reasoning: bool,
+    /// Stdout is a terminal: reasoning is dimmed and a notice shows while the
+    /// model thinks. Off when the answer is piped, so it stays plain text.
+    pub terminal: bool,
 }
 
-async fn process_openai_request(client: ApiClient, prompt: &str) {
-    match client.openai_stream_request(prompt).await {
-        Ok(mut stream) => {
-            while let Some(result) = stream.next().await {
-                match result {
-                    Ok(response) => {
-                        print!("{}", response);
-                    },
-                    Err(e) => {
-                        error!("Error: {}", e);
-                    },
-                }
-            }
-        }
+/// The system prompt, then any few-shot examples.
+pub fn build_context(adapter_config: &AdapterConfig, examples: &[ChatMessage]) -> Vec<ChatMessage> {
+    let mut messages = vec![ChatMessage::system(adapter_config.system_prompt().unwrap_or(DEFAULT_SYSTEM_PROMPT))];
+    messages.extend_from_slice(examples);
+    messages
+}
+
+/// The system prompt, any few-shot examples, the earlier conversation, then
+/// the question.
+pub fn build_messages(adapter_config: &AdapterConfig, examples: &[ChatMessage], history: &[ChatMessage], prompt: &str) -> Vec<ChatMessage> {
+    let mut messages = build_context(adapter_config, examples);
+    messages.extend_from_slice(history);
+    messages.push(ChatMessage::user(prompt));
+    messages
+}
+
+/// A streamed answer, as returned by `render_stream`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Answer {
+    pub text: String,
+    /// The model's reasoning, whether or not it was shown.
+    pub reasoning: String,
+    /// Token counts, if the provider reported them.
+    pub usage: Option<Usage>,
+}
+
+/// Sends the conversation and prints the answer. Returns the answer, or
+/// `None` if the request failed.
+pub async fn process_response_stream(adapter_config: &AdapterConfig, messages: &[ChatMessage], options: &RenderOptions) -> Option<Answer> {
+    let client = ApiClient::new(adapter_config.clone());
+
+    match client.stream_chat(messages).await {
+        Ok(stream) => {
+            Some(render_stream(stream, options).await)
+        },
         Err(e) => {
-            error!("Error processing OpenAI request: {}", e);
+            error!("Error processing request: {}", e);
+            eprintln!("Error: {}", e);
+            None
         }
     }
 }
 
-async fn process_claude_request(client: ApiClient, prompt: &str) {
-    match client.claude_stream_request(prompt).await {
-        Ok(mut stream) => {
-            while let Some(chunk) = stream.next().await {
-                match chunk {
-                    Ok(content) => {
-                        println!("{}", content);
-                    }
-                    Err(err) => {
-                        error!("Error receiving stream chunk: {}", err);
-                    }
-                }
-            }
-        }
-        Err(err) => {
-            error!("Error processing Claude request: {}", err);
+/// Sends the conversation and returns the answer's text without printing
+/// anything, for requests the user doesn't see such as summaries.
+pub async fn complete(adapter_config: &AdapterConfig, messages: &[ChatMessage]) -> Result<String, Box<dyn std::error::Error>> {
+    let mut stream = ApiClient::new(adapter_config.clone()).stream_chat(messages).await?;
+    let mut text = String::new();
+    while let Some(result) = stream.next().await {
+        if let StreamEvent::Text(chunk) = result? {
+            text.push_str(&chunk);
         }
     }
+    Ok(text)
+}
+
+/// Restores the terminal after a stream was dropped half way through.
+pub fn finish_cancelled_stream() {
+    print!("{}", RESET);
+    println!();
+    eprintln!("{}(cancelled){}", DIM, RESET);
 }
 
-async fn process_zhipu_request(client: ApiClient, prompt: &str) {
-    match client.zhipu_stream_request(prompt).await {
-        Ok(mut stream) => {
-            while let Some(chunk) = stream.next().await {
-                match chunk {
-                    Ok(content) => {
-                        println!("{}", content);
+/// Prints the answer as it arrives and returns the full text. Reasoning is
+/// kept apart from the text, so it is not sent back to the model.
+pub async fn render_stream(mut stream: EventStream, options: &RenderOptions) -> Answer {
+    let mut answer = Answer::default();
+    let mut stdout = std::io::stdout();
+    let mut in_reasoning = false;
+    let mut thinking_notice = false;
+
+    while let Some(result) = stream.next().await {
+        match result {
+            Ok(StreamEvent::Reasoning(text)) => {
+                answer.reasoning.push_str(&text);
+                if options.show_reasoning {
+                    if !in_reasoning {
+                        if options.terminal {
+                            print!("{}", DIM);
+                        }
+                        in_reasoning = true;
                     }
-                    Err(err) => {
-                        error!("Error receiving stream chunk: {}", err);
+                    print!("{}", text);
+                } else if options.terminal && !thinking_notice {
+                    // Let the user know the model is working on it
+                    eprint!("{}Thinking...{}", DIM, RESET);
+                    thinking_notice = true;
+                }
+                let _ = stdout.flush();
+            },
+            Ok(StreamEvent::Text(text)) => {
+                if in_reasoning {
+                    if options.terminal {
+                        print!("{}", RESET);
                     }
+                    print!("\n\n");
+                    in_reasoning = false;
                 }
-            }
-        }
-        Err(err) => {
-            error!("Error processing Claude request: {}", err);
+                if thinking_notice {
+                    eprint!("{}", CLEAR_LINE);
+                    thinking_notice = false;
+                }
+                print!("{}", text);
+                let _ = stdout.flush();
+                answer.text.push_str(&text);
+            },
+            Ok(StreamEvent::Usage(usage)) => *answer.usage.get_or_insert_default() += usage,
+            Ok(StreamEvent::Done(_)) => {},
+            Err(e) => {
+                error!("Error receiving stream chunk: {}", e);
+                eprintln!("\nError: {}", e);
+            },
         }
     }
-}
\ No newline at end of file
+    if in_reasoning && options.terminal {
+        print!("{}", RESET);
+    }
+    if thinking_notice {
+        eprint!("{}", CLEAR_LINE);
+    }
+    println!();
+
+    answer
+}
@@ -0,0 +1,272 @@
+//! Files attached to a question with `-f/--file`. Each one is sent under a
+//! header with its path, in a code fence tagged with its language.
+//! Directories and globs are walked honouring `.gitignore`.
+
+use globset::GlobBuilder;
+use ignore::WalkBuilder;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use crate::config::InputConfig;
+use crate::context;
+use crate::session_export::fence;
+
+pub const DEFAULT_MAX_FILE_BYTES: usize = 100 * 1024;
+pub const DEFAULT_MAX_TOTAL_FILE_BYTES: usize = 512 * 1024;
+/// Attachments estimated above this many tokens are reported before sending.
+const LARGE_ATTACHMENT_TOKENS: u32 = 8_000;
+/// Bytes checked for NULs when telling text from binary files.
+const BINARY_CHECK_BYTES: usize = 8 * 1024;
+/// Files listed by name in the warning; the rest are counted.
+const MAX_LISTED: usize = 20;
+
+#[derive(Debug, Default)]
+pub struct Attachments {
+    /// The files, formatted to follow the question.
+    pub text: String,
+    pub files: usize,
+    /// Files that were cut short or left out, and why.
+    pub problems: Vec<(PathBuf, String)>,
+}
+
+impl Attachments {
+    /// Warns about truncated or skipped files, and gives an estimate of the
+    /// size when the attachments are large.
+    pub fn report(&self) {
+        if !self.problems.is_empty() {
+            eprintln!("Warning: some attached files were truncated or skipped:");
+            for (path, reason) in self.problems.iter().take(MAX_LISTED) {
+                eprintln!("  {}: {}", path.display(), reason);
+            }
+            if self.problems.len() > MAX_LISTED {
+                eprintln!("  ... and {} more", self.problems.len() - MAX_LISTED);
+            }
+        }
+        let tokens = context::estimate_text_tokens(&self.text);
+        if tokens > LARGE_ATTACHMENT_TOKENS {
+            eprintln!("Note: attaching {} file(s), about {} tokens", self.files, tokens);
+        }
+    }
+}
+
+/// Reads the files named by `patterns`, within the size limits in `config`.
+pub fn attach(patterns: &[String], config: &InputConfig) -> Result<Attachments, Box<dyn Error>> {
+    let max_file = config.max_file_bytes.unwrap_or(DEFAULT_MAX_FILE_BYTES);
+    let mut remaining = config.max_total_file_bytes.unwrap_or(DEFAULT_MAX_TOTAL_FILE_BYTES);
+
+    let mut paths: Vec<PathBuf> = Vec::new();
+    for pattern in patterns {
+        for path in expand(pattern)? {
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+
+    let mut attachments = Attachments::default();
+    for path in paths {
+        if remaining == 0 {
+            attachments.problems.push((path, "skipped, over the total size limit".to_string()));
+            continue;
+        }
+        let limit = max_file.min(remaining);
+        match read_text(&path, limit) {
+            Ok(Some((text, truncated))) => {
+                // Once the total limit cuts a file short, nothing more fits
+                remaining = if truncated && limit == remaining { 0 } else { remaining - text.len() };
+                if truncated {
+                    attachments.problems.push((path.clone(), format!("truncated to {} bytes", text.len())));
+                }
+                attachments.text.push_str(&format_file(&path, &text, truncated));
+                attachments.files += 1;
+            },
+            Ok(None) => attachments.problems.push((path, "skipped, binary".to_string())),
+            Err(e) => attachments.problems.push((path, format!("skipped, {}", e))),
+        }
+    }
+    Ok(attachments)
+}
+
+fn has_wildcards(text: &str) -> bool {
+    text.contains(['*', '?', '[', '{'])
+}
+
+/// The files a pattern names: a file, every file under a directory, or
+/// every file matching a glob.
+fn expand(pattern: &str) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let path = Path::new(pattern);
+    if path.is_file() {
+        // Named explicitly, so it is attached even if ignored
+        return Ok(vec![path.to_path_buf()]);
+    }
+    if path.is_dir() {
+        return Ok(walk(path, |_| true));
+    }
+    if !has_wildcards(pattern) {
+        return Err(format!("{}: no such file or directory", pattern).into());
+    }
+
+    // Walk from the part of the pattern before the first wildcard
+    let pattern = pattern.strip_prefix("./").unwrap_or(pattern);
+    let base: PathBuf = Path::new(pattern).components()
+        .take_while(|component| !has_wildcards(&component.as_os_str().to_string_lossy()))
+        .collect();
+    let root = if base.as_os_str().is_empty() { Path::new(".") } else { base.as_path() };
+    let glob = GlobBuilder::new(pattern).literal_separator(true).build()?.compile_matcher();
+    let files = walk(root, |file| glob.is_match(file));
+    if files.is_empty() {
+        return Err(format!("No files match '{}'", pattern).into());
+    }
+    Ok(files)
+}
+
+/// Files under `root` accepted by `keep`, leaving out hidden files and
+/// whatever `.gitignore` excludes.
+fn walk(root: &Path, keep: impl Fn(&Path) -> bool) -> Vec<PathBuf> {
+    WalkBuilder::new(root)
+        .require_git(false)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .build()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_some_and(|file_type| file_type.is_file()))
+        .map(|entry| entry.path().strip_prefix(".").unwrap_or(entry.path()).to_path_buf())
+        .filter(|path| keep(path))
+        .collect()
+}
+
+/// Up to `limit` bytes of the file, and whether it was cut short. `None`
+/// for binary files.
+fn read_text(path: &Path, limit: usize) -> io::Result<Option<(String, bool)>> {
+    let mut bytes = Vec::new();
+    File::open(path)?.take(limit as u64 + 1).read_to_end(&mut bytes)?;
+    let truncated = bytes.len() > limit;
+    bytes.truncate(limit);
+    if bytes[..bytes.len().min(BINARY_CHECK_BYTES)].contains(&0) {
+        return Ok(None);
+    }
+    match String::from_utf8(bytes) {
+        Ok(text) => Ok(Some((text, truncated))),
+        // The cut may split a multi-byte character; anything else isn't text
+        Err(e) if truncated && e.utf8_error().error_len().is_none() => {
+            let valid = e.utf8_error().valid_up_to();
+            let mut bytes = e.into_bytes();
+            bytes.truncate(valid);
+            Ok(Some((String::from_utf8(bytes).unwrap(), true)))
+        },
+        Err(_) => Ok(None),
+    }
+}
+
+fn format_file(path: &Path, text: &str, truncated: bool) -> String {
+    let fence = fence(text);
+    format!(
+        "File: {}{}\n{}{}\n{}\n{}\n\n",
+        path.display(), if truncated { " (truncated)" } else { "" },
+        fence, language(path), text.trim_end_matches('\n'), fence
+    )
+}
+
+/// The code fence tag for a file, from its name.
+fn language(path: &Path) -> &'static str {
+    match path.file_name().and_then(|name| name.to_str()) {
+        Some("Dockerfile") => return "dockerfile",
+        Some("Makefile" | "makefile") => return "makefile",
+        Some("CMakeLists.txt") => return "cmake",
+        _ => {},
+    }
+    let extension = path.extension().and_then(|extension| extension.to_str()).map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("rs") => "rust",
+        Some("py") => "python",
+        Some("js" | "mjs" | "cjs") => "javascript",
+        Some("jsx") => "jsx",
+        Some("ts") => "typescript",
+        Some("tsx") => "tsx",
+        Some("go") => "go",
+        Some("c" | "h") => "c",
+        Some("cc" | "cpp" | "cxx" | "hpp" | "hh") => "cpp",
+        Some("cs") => "csharp",
+        Some("java") => "java",
+        Some("kt" | "kts") => "kotlin",
+        Some("swift") => "swift",
+        Some("rb") => "ruby",
+        Some("php") => "php",
+        Some("lua") => "lua",
+        Some("sh" | "bash") => "bash",
+        Some("zsh") => "zsh",
+        Some("ps1") => "powershell",
+        Some("sql") => "sql",
+        Some("html" | "htm") => "html",
+        Some("css") => "css",
+        Some("scss") => "scss",
+        Some("json") => "json",
+        Some("toml") => "toml",
+        Some("yaml" | "yml") => "yaml",
+        Some("xml") => "xml",
+        Some("md") => "markdown",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn project(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ruskgpt-attach-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("src/nested")).unwrap();
+        fs::write(dir.join(".gitignore"), "*.log\n").unwrap();
+        fs::write(dir.join("build.log"), "ignored\n").unwrap();
+        fs::write(dir.join("README.md"), "# Demo\n").unwrap();
+        fs::write(dir.join("src/main.rs"), "fn main() {}\n").unwrap();
+        fs::write(dir.join("src/nested/util.rs"), "pub fn util() {}\n").unwrap();
+        fs::write(dir.join("src/logo.png"), b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+        fs::write(dir.join("src/notes.txt"), "é".repeat(100)).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_globs_and_directories() {
+        let dir = project("globs");
+        let paths: Vec<PathBuf> = expand(&format!("{}/src/**/*.rs", dir.display())).unwrap();
+        assert_eq!(paths, vec![dir.join("src/main.rs"), dir.join("src/nested/util.rs")]);
+        // `*` stays within one directory
+        assert_eq!(expand(&format!("{}/src/*.rs", dir.display())).unwrap(), vec![dir.join("src/main.rs")]);
+        // .gitignore is honoured when walking, but not for files named outright
+        let paths = expand(dir.to_str().unwrap()).unwrap();
+        assert!(!paths.contains(&dir.join("build.log")) && paths.contains(&dir.join("README.md")));
+        assert_eq!(expand(dir.join("build.log").to_str().unwrap()).unwrap(), vec![dir.join("build.log")]);
+        assert!(expand(&format!("{}/*.py", dir.display())).unwrap_err().to_string().starts_with("No files match"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_attach_limits() {
+        let dir = project("limits");
+        let config = InputConfig { max_file_bytes: Some(51), max_total_file_bytes: Some(88), ..Default::default() };
+        let patterns = vec![
+            dir.join("README.md").display().to_string(),
+            format!("{}/src", dir.display()),
+            dir.join(".gitignore").display().to_string(),
+        ];
+        let attachments = attach(&patterns, &config).unwrap();
+
+        assert_eq!(attachments.files, 4);
+        assert!(attachments.text.starts_with(&format!(
+            "File: {}\n```markdown\n# Demo\n```\n\nFile: {}\n```rust\nfn main() {{}}\n```\n\n",
+            dir.join("README.md").display(), dir.join("src/main.rs").display()
+        )));
+        // Cut at 51 bytes, inside an "é"
+        assert!(attachments.text.contains(&format!("File: {} (truncated)\n```\n{}\n```", dir.join("src/notes.txt").display(), "é".repeat(25))));
+        let problems: Vec<(PathBuf, &str)> = attachments.problems.iter().map(|(path, reason)| (path.clone(), reason.as_str())).collect();
+        assert_eq!(problems, vec![
+            (dir.join("src/logo.png"), "skipped, binary"),
+            (dir.join("src/notes.txt"), "truncated to 50 bytes"),
+            (dir.join(".gitignore"), "skipped, over the total size limit"),
+        ]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
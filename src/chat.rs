@@ -0,0 +1,413 @@
+//! Interactive multi-turn chat, started with `ruskgpt chat` or by running
+//! `ruskgpt` without a question on a terminal. Every turn is kept and sent
+//! again with the next question.
+
+use log::warn;
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use crate::api::{self, RenderOptions};
+use crate::config::{AdapterConfig, Config, RoleConfig};
+use crate::config_handler;
+use crate::context;
+use crate::overrides::GenerationOverrides;
+use crate::paths::Paths;
+use crate::providers::ChatMessage;
+use crate::session_export;
+use crate::sessions::{Session, SessionMessage, SessionStore};
+
+const HELP: &str = "\
+/model [name]      show or switch the model
+/adapter [name]    show or switch the adapter
+/system [prompt]   show or replace the system prompt
+/clear             start a new session
+/save <file>       export the session (.md, .html or .json)
+/exit              leave (Ctrl-D works too)
+
+End a line with \\ to continue on the next one, or wrap several lines in \"\"\".
+Start a message with // to send a leading slash. Ctrl-C cancels a streaming answer.";
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Model(Option<String>),
+    Adapter(Option<String>),
+    System(Option<String>),
+    Clear,
+    Save(String),
+    Help,
+    Exit,
+}
+
+fn parse_command(line: &str) -> Result<Command, String> {
+    let (name, arg) = match line.split_once(char::is_whitespace) {
+        Some((name, arg)) => (name, Some(arg.trim().to_string()).filter(|arg| !arg.is_empty())),
+        None => (line, None),
+    };
+    match name {
+        "/model" => Ok(Command::Model(arg)),
+        "/adapter" => Ok(Command::Adapter(arg)),
+        "/system" => Ok(Command::System(arg)),
+        "/clear" => Ok(Command::Clear),
+        "/save" => arg.map(Command::Save).ok_or_else(|| "Usage: /save <file>".to_string()),
+        "/help" | "/?" => Ok(Command::Help),
+        "/exit" | "/quit" => Ok(Command::Exit),
+        _ => Err(format!("Unknown command {}. Type /help for the list.", name)),
+    }
+}
+
+/// Collects the lines of one prompt: a trailing `\` continues on the next
+/// line, and `"""` opens a block that runs until the closing `"""`.
+#[derive(Debug, Default)]
+struct Input {
+    lines: Vec<String>,
+    in_block: bool,
+}
+
+impl Input {
+    /// Adds a line and returns whether the prompt is complete.
+    fn push(&mut self, line: &str) -> bool {
+        if line.trim() == "\"\"\"" {
+            if self.in_block {
+                self.in_block = false;
+                return true;
+            }
+            if self.lines.is_empty() {
+                self.in_block = true;
+                return false;
+            }
+        }
+        if self.in_block {
+            self.lines.push(line.to_string());
+            return false;
+        }
+        match line.strip_suffix('\\') {
+            Some(line) => {
+                self.lines.push(line.to_string());
+                false
+            },
+            None => {
+                self.lines.push(line.to_string());
+                true
+            },
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.lines.is_empty() && !self.in_block
+    }
+
+    fn take(&mut self) -> String {
+        self.in_block = false;
+        std::mem::take(&mut self.lines).join("\n")
+    }
+}
+
+pub struct Chat<'a> {
+    config: &'a Config,
+    role: Option<(String, RoleConfig)>,
+    /// Command line overrides, plus any `/system` set during the chat.
+    overrides: GenerationOverrides,
+    adapter_name: String,
+    adapter: AdapterConfig,
+    /// The system prompt and the role's examples, then the conversation.
+    messages: Vec<ChatMessage>,
+    options: RenderOptions,
+    store: SessionStore,
+    /// Where each exchange is recorded.
+    session: Session,
+}
+
+/// How the chat is answered.
+pub struct ChatSettings<'a> {
+    pub config: &'a Config,
+    pub adapter_name: String,
+    /// Already prepared with `config_handler::prepare_adapter_config`.
+    pub adapter: AdapterConfig,
+    pub role: Option<(String, RoleConfig)>,
+    pub overrides: GenerationOverrides,
+    pub options: RenderOptions,
+}
+
+impl<'a> Chat<'a> {
+    /// Starts a chat that carries on from `session`'s conversation.
+    pub fn new(settings: ChatSettings<'a>, store: SessionStore, session: Session) -> Self {
+        let ChatSettings { config, adapter_name, adapter, role, overrides, options } = settings;
+        let mut chat = Chat { config, role, overrides, adapter_name, adapter, messages: Vec::new(), options, store, session };
+        chat.messages = api::build_context(&chat.adapter, chat.examples());
+        chat.messages.extend(chat.session.chat_messages());
+        chat
+    }
+
+    fn examples(&self) -> &[ChatMessage] {
+        self.role.as_ref().map(|(_, role)| role.examples.as_slice()).unwrap_or_default()
+    }
+
+    /// Forgets the conversation and records the next one as a new session.
+    fn clear(&mut self) {
+        self.messages = api::build_context(&self.adapter, self.examples());
+        self.session = self.store.start();
+    }
+
+    fn set_system_prompt(&mut self) {
+        self.messages[0] = ChatMessage::system(self.adapter.system_prompt().unwrap_or(api::DEFAULT_SYSTEM_PROMPT));
+    }
+
+    fn run_command(&mut self, command: Command) -> Result<(), Box<dyn Error>> {
+        match command {
+            Command::Model(None) => println!("{}", self.adapter.model()),
+            Command::Model(Some(model)) => {
+                GenerationOverrides { model: Some(model), ..Default::default() }.apply(&mut self.adapter)?;
+                eprintln!("Model set to {}", self.adapter.model());
+            },
+            Command::Adapter(None) => {
+                for name in self.config.adapters.keys() {
+                    let marker = if *name == self.adapter_name { "*" } else { " " };
+                    println!("{} {}", marker, name);
+                }
+            },
+            Command::Adapter(Some(name)) => {
+                let base = self.config.adapters.get(&name).ok_or_else(|| {
+                    let valid_names = self.config.adapters.keys().cloned().collect::<Vec<_>>();
+                    format!("Unknown adapter '{}'. Valid adapters: {}", name, valid_names.join(", "))
+                })?;
+                // The new adapter brings its own model
+                let overrides = GenerationOverrides { model: None, ..self.overrides.clone() };
+                let role = self.role.as_ref().map(|(name, role)| (name.as_str(), role));
+                self.adapter = config_handler::prepare_adapter_config(base, self.config, role, &overrides)?;
+                self.adapter_name = name;
+                self.set_system_prompt();
+                eprintln!("Switched to {} ({})", self.adapter_name, self.adapter.model());
+            },
+            Command::System(None) => println!("{}", self.messages[0].content),
+            Command::System(Some(system)) => {
+                let overrides = GenerationOverrides { system: Some(system.clone()), ..Default::default() };
+                overrides.apply(&mut self.adapter)?;
+                self.overrides.system = Some(system);
+                self.set_system_prompt();
+                eprintln!("System prompt replaced");
+            },
+            Command::Clear => {
+                self.clear();
+                eprintln!("Conversation cleared; new session {}", self.session.name);
+            },
+            Command::Save(path) => {
+                let format = session_export::Format::from_path(Path::new(&path));
+                fs::write(&path, session_export::export(&self.session, format)?)?;
+                eprintln!("Saved to {}", path);
+            },
+            Command::Help => println!("{}", HELP),
+            Command::Exit => {},
+        }
+        Ok(())
+    }
+
+    /// Sends `input` with the conversation so far and records the exchange
+    /// in the session. Ctrl-C stops the answer and drops the question, so the
+    /// next one doesn't follow an unanswered turn.
+    async fn send(&mut self, input: &str) {
+        let question = SessionMessage::question(input);
+        // Start again from the session, which has any summary of older turns;
+        // turns left out to fit the last question stay in the file
+        let keep = 1 + self.examples().len();
+        self.messages.truncate(keep);
+        self.messages.extend(self.session.chat_messages());
+        self.messages.push(question.message.clone());
+        let summary = match context::fit(&mut self.messages, keep, &self.adapter, &self.config.context).await {
+            Ok(Some(compaction)) => {
+                eprintln!("Note: {}", compaction.note);
+                compaction.summary
+            },
+            Ok(None) => None,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                self.messages.pop();
+                return;
+            },
+        };
+        let answer = tokio::select! {
+            answer = api::process_response_stream(&self.adapter, &self.messages, &self.options) => answer,
+            _ = tokio::signal::ctrl_c() => {
+                api::finish_cancelled_stream();
+                None
+            },
+        };
+        let answer = match answer {
+            Some(answer) if !answer.text.is_empty() => answer,
+            _ => {
+                self.messages.pop();
+                return;
+            },
+        };
+
+        self.messages.push(ChatMessage::assistant(answer.text.clone()));
+        let mut exchange: Vec<_> = summary.map(|(summary, replaced)| self.session.summary(summary, replaced)).into_iter().collect();
+        exchange.push(question);
+        exchange.push(SessionMessage::answer(answer, &self.adapter_name, self.adapter.model()));
+        match self.store.save(&self.session, exchange.clone()) {
+            Ok(session) => self.session = session,
+            Err(e) => {
+                eprintln!("Warning: could not save session {}: {}", self.session.name, e);
+                // Keep it for the rest of the chat
+                for message in exchange {
+                    self.session.push(message);
+                }
+            },
+        }
+    }
+}
+
+/// Reads one prompt from the terminal. Returns `None` at end of input.
+fn read_input(editor: &mut DefaultEditor) -> Result<Option<String>, ReadlineError> {
+    let mut input = Input::default();
+    loop {
+        let prompt = if input.is_empty() { ">>> " } else { "... " };
+        match editor.readline(prompt) {
+            Ok(line) => {
+                if input.push(&line) {
+                    break;
+                }
+            },
+            // Ctrl-C at the prompt discards what was typed
+            Err(ReadlineError::Interrupted) => return Ok(Some(String::new())),
+            Err(ReadlineError::Eof) => return Ok(None),
+            Err(e) => return Err(e),
+        }
+    }
+    let input = input.take();
+    if !input.trim().is_empty() {
+        let _ = editor.add_history_entry(input.as_str());
+    }
+    Ok(Some(input))
+}
+
+/// Runs the REPL until `/exit` or Ctrl-D. Returns the process exit code.
+pub async fn run(mut chat: Chat<'_>) -> i32 {
+    let mut editor = match DefaultEditor::new() {
+        Ok(editor) => editor,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return 1;
+        }
+    };
+    let history_path = Paths::new().state_dir.join("history");
+    let _ = editor.load_history(&history_path);
+
+    if !chat.session.messages.is_empty() {
+        eprintln!("Continuing session {} ({} messages)", chat.session.name, chat.session.messages.len());
+    }
+    eprintln!(
+        "Chatting with {} ({}). Type /help for commands, /exit to leave.",
+        chat.adapter_name, chat.adapter.model()
+    );
+    loop {
+        let input = match read_input(&mut editor) {
+            Ok(Some(input)) => input,
+            Ok(None) => break,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                break;
+            }
+        };
+        let input = input.trim_end();
+        if input.trim().is_empty() {
+            continue;
+        }
+
+        if let Some(message) = input.strip_prefix("//") {
+            chat.send(&format!("/{}", message)).await;
+        } else if input.starts_with('/') {
+            match parse_command(input.trim()) {
+                Ok(Command::Exit) => break,
+                Ok(command) => {
+                    if let Err(e) = chat.run_command(command) {
+                        eprintln!("Error: {}", e);
+                    }
+                },
+                Err(e) => eprintln!("{}", e),
+            }
+        } else {
+            chat.send(input).await;
+        }
+    }
+
+    if let Some(dir) = history_path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    if let Err(e) = editor.save_history(&history_path) {
+        warn!("Could not save chat history to {}: {}", history_path.display(), e);
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(parse_command("/model gpt-4o"), Ok(Command::Model(Some("gpt-4o".to_string()))));
+        assert_eq!(parse_command("/model  "), Ok(Command::Model(None)));
+        assert_eq!(parse_command("/system Be brief. Answer in French."), Ok(Command::System(Some("Be brief. Answer in French.".to_string()))));
+        assert_eq!(parse_command("/save"), Err("Usage: /save <file>".to_string()));
+        assert_eq!(parse_command("/quit"), Ok(Command::Exit));
+        assert!(parse_command("/frobnicate").is_err());
+    }
+
+    #[test]
+    fn test_multi_line_input() {
+        let mut input = Input::default();
+        assert!(!input.push("first \\"));
+        assert!(input.push("second"));
+        assert_eq!(input.take(), "first \nsecond");
+
+        assert!(!input.push("\"\"\""));
+        assert!(!input.is_empty());
+        assert!(!input.push("fn main() {"));
+        assert!(!input.push("    println!(\"hi\"); \\"));
+        assert!(!input.push("}"));
+        assert!(input.push("\"\"\""));
+        assert_eq!(input.take(), "fn main() {\n    println!(\"hi\"); \\\n}");
+        assert!(input.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_turns_accumulate() {
+        let config = Config::from_file("config.toml.example").unwrap();
+        let body = "data: {\"choices\":[{\"delta\":{\"content\":\"Paris.\"}}]}\n\ndata: [DONE]\n\n";
+        let (base_url, request) = crate::providers::mock_server::serve_once(200, "text/event-stream", body).await;
+        let mut adapter = config.adapters["openai_adapter"].clone();
+        let AdapterConfig::OpenAI(openai) = &mut adapter else { unreachable!() };
+        openai.base_url = base_url;
+        openai.token = "sk-test".to_string();
+
+        let settings = ChatSettings {
+            config: &config,
+            adapter_name: "openai_adapter".to_string(),
+            adapter,
+            role: None,
+            overrides: GenerationOverrides::default(),
+            options: RenderOptions::default(),
+        };
+        let dir = std::env::temp_dir().join(format!("ruskgpt-chat-{}", std::process::id()));
+        let mut session = Session::new("geography");
+        session.messages.push(SessionMessage::question("Capital of Italy?"));
+        session.messages.push(SessionMessage::new(ChatMessage::assistant("Rome."), chrono::Utc::now()));
+        let mut chat = Chat::new(settings, SessionStore::new(dir.clone()), session);
+        chat.send("And France?").await;
+
+        let request = request.await.unwrap();
+        assert!(request.contains("\"Capital of Italy?\"") && request.contains("\"Rome.\""));
+        assert_eq!(chat.messages.last(), Some(&ChatMessage::assistant("Paris.")));
+        // Only the new exchange is appended to the stored session
+        let stored = SessionStore::new(dir.clone()).load("geography").unwrap();
+        assert_eq!(stored.chat_messages(), vec![ChatMessage::user("And France?"), ChatMessage::assistant("Paris.")]);
+
+        chat.run_command(Command::Clear).unwrap();
+        // Only the system prompt is left
+        assert_eq!(chat.messages.len(), 1);
+        assert_ne!(chat.session.name, "geography");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,18 +1,92 @@
 use clap::{Arg, Command};
 
 pub fn parse_command_line_arguments() -> clap::ArgMatches {
+    build_cli().get_matches()
+}
+
+pub fn build_cli() -> Command {
     Command::new(env!("CARGO_PKG_NAME"))
         .version(env!("CARGO_PKG_VERSION"))
         .author(env!("CARGO_PKG_AUTHORS"))
         .about(env!("CARGO_PKG_DESCRIPTION"))
         .arg(Arg::new("question")
-            .help("The question to ask GPT")
+            .help("The question to ask GPT; `-` reads it from stdin. Text piped to stdin is sent along with it")
             .index(1))
         .arg(Arg::new("config")
             .long("config")
             .short('c')
             .value_parser(clap::value_parser!(String))
+            .global(true)
             .help("Path to the configuration file"))
+        .arg(Arg::new("adapter")
+            .long("adapter")
+            .short('a')
+            .value_parser(clap::value_parser!(String))
+            .global(true)
+            .help("Name of the adapter to use, overriding default.adapter"))
+        .arg(Arg::new("role")
+            .long("role")
+            .short('r')
+            .value_parser(clap::value_parser!(String))
+            .global(true)
+            .help("Role to answer as, e.g. shell, code, explain or translate"))
+        .arg(Arg::new("model")
+            .long("model")
+            .short('m')
+            .value_parser(clap::value_parser!(String))
+            .global(true)
+            .help("Model to use for this question"))
+        .arg(Arg::new("temperature")
+            .long("temperature")
+            .short('t')
+            .value_parser(clap::value_parser!(f32))
+            .global(true)
+            .help("Sampling temperature"))
+        .arg(Arg::new("top-p")
+            .long("top-p")
+            .value_parser(clap::value_parser!(f32))
+            .global(true)
+            .help("Nucleus sampling probability mass"))
+        .arg(Arg::new("max-tokens")
+            .long("max-tokens")
+            .value_parser(clap::value_parser!(u32))
+            .global(true)
+            .help("Maximum number of tokens to generate"))
+        .arg(Arg::new("seed")
+            .long("seed")
+            .value_parser(clap::value_parser!(u64))
+            .global(true)
+            .help("Seed for deterministic sampling, where supported"))
+        .arg(Arg::new("stop")
+            .long("stop")
+            .value_parser(clap::value_parser!(String))
+            .action(clap::ArgAction::Append)
+            .global(true)
+            .help("Stop sequence (can be repeated)"))
+        .arg(Arg::new("system")
+            .long("system")
+            .short('s')
+            .value_parser(clap::value_parser!(String))
+            .global(true)
+            .help("System prompt for this question"))
+        .arg(Arg::new("file")
+            .long("file")
+            .short('f')
+            .value_name("PATH_OR_GLOB")
+            .value_parser(clap::value_parser!(String))
+            .action(clap::ArgAction::Append)
+            .help("Attach a file, directory or glob such as 'src/**/*.rs' to the question (can be repeated)"))
+        .arg(Arg::new("continue")
+            .long("continue")
+            .action(clap::ArgAction::SetTrue)
+            .conflicts_with("session")
+            .global(true)
+            .help("Continue the most recent session"))
+        .arg(Arg::new("session")
+            .long("session")
+            .value_parser(clap::value_parser!(String))
+            .global(true)
+            .help("Continue the named session, or start it if it doesn't exist"))
         .arg(Arg::new("edit")
             .short('e')
             .long("edit")
@@ -23,5 +97,119 @@ pub fn parse_command_line_arguments() -> clap::ArgMatches {
             .short('l')
             .help("List all available functions")
             .action(clap::ArgAction::SetTrue))
-        .get_matches()
+        .arg(Arg::new("show-reasoning")
+            .long("show-reasoning")
+            .global(true)
+            .help("Print the reasoning of thinking models (dimmed) before the answer")
+            .action(clap::ArgAction::SetTrue))
+        .arg(Arg::new("agent")
+            .long("agent")
+            .help("Enable the function calling workflow (not yet implemented)")
+            .action(clap::ArgAction::SetTrue))
+        .subcommand(Command::new("config")
+            .about("Inspect, validate and edit the configuration file")
+            .subcommand_required(true)
+            .subcommand(Command::new("check")
+                .about("Report syntax errors, unknown keys and invalid values"))
+            .subcommand(Command::new("show")
+                .about("Print the effective configuration after merging all layers")
+                .arg(Arg::new("origin")
+                    .long("origin")
+                    .help("Show which layer each value came from")
+                    .action(clap::ArgAction::SetTrue)))
+            .subcommand(Command::new("get")
+                .about("Print the value of a key, e.g. adapters.work.default_model")
+                .arg(Arg::new("key").required(true)))
+            .subcommand(Command::new("set")
+                .about("Set a key, keeping the comments and layout of the file")
+                .arg(Arg::new("key").required(true))
+                .arg(Arg::new("value").required(true)))
+            .subcommand(Command::new("unset")
+                .about("Remove a key")
+                .arg(Arg::new("key").required(true))))
+        .subcommand(Command::new("roles")
+            .about("List and inspect roles")
+            .subcommand_required(true)
+            .subcommand(Command::new("list")
+                .about("List built-in and configured roles"))
+            .subcommand(Command::new("show")
+                .about("Print a role's settings")
+                .arg(Arg::new("name").required(true))))
+        .subcommand(Command::new("chat")
+            .about("Start an interactive chat (the default without a question)"))
+        .subcommand(Command::new("sessions")
+            .about("List and manage saved conversations")
+            .subcommand_required(true)
+            .subcommand(Command::new("list")
+                .about("List sessions, most recent first"))
+            .subcommand(Command::new("show")
+                .about("Print a session's conversation")
+                .arg(Arg::new("name").required(true)))
+            .subcommand(Command::new("export")
+                .about("Export a session as Markdown, HTML or JSON")
+                .arg(Arg::new("name").required(true))
+                .arg(Arg::new("format")
+                    .long("format")
+                    .value_parser(["md", "html", "json"])
+                    .default_value("md")
+                    .help("Output format"))
+                .arg(Arg::new("output")
+                    .long("output")
+                    .short('o')
+                    .help("File to write instead of standard output")))
+            .subcommand(Command::new("import")
+                .about("Import conversations from a ChatGPT data export")
+                .arg(Arg::new("file")
+                    .required(true)
+                    .help("The export zip, or the conversations.json inside it")))
+            .subcommand(Command::new("delete")
+                .about("Delete a session")
+                .arg(Arg::new("name").required(true)))
+            .subcommand(Command::new("rename")
+                .about("Rename a session")
+                .arg(Arg::new("name").required(true))
+                .arg(Arg::new("new-name").required(true))))
+        .subcommand(Command::new("paths")
+            .about("Print where config, data, cache and logs are kept"))
+        .subcommand(Command::new("init")
+            .about("Set up an adapter, test it and save it as the default")
+            .arg(Arg::new("non-interactive")
+                .long("non-interactive")
+                .help("Take every answer from the flags below instead of asking")
+                .action(clap::ArgAction::SetTrue))
+            .arg(Arg::new("provider")
+                .long("provider")
+                .help("OpenAI, Claude, Zhipu, Gemini, Ollama or AzureOpenAI"))
+            .arg(Arg::new("name")
+                .long("name")
+                .help("Adapter name [default: the provider in lowercase]"))
+            .arg(Arg::new("base-url")
+                .long("base-url")
+                .help("API base URL, e.g. a local mock or proxy"))
+            .arg(Arg::new("deployment")
+                .long("deployment")
+                .help("Azure OpenAI deployment name"))
+            .arg(Arg::new("api-version")
+                .long("api-version")
+                .help("Azure OpenAI API version"))
+            .arg(Arg::new("token")
+                .long("token")
+                .help("API key, stored in plaintext"))
+            .arg(Arg::new("token-env")
+                .long("token-env")
+                .help("Environment variable holding the API key"))
+            .arg(Arg::new("token-cmd")
+                .long("token-cmd")
+                .help("Command that prints the API key"))
+            .arg(Arg::new("token-file")
+                .long("token-file")
+                .help("File holding the API key"))
+            .arg(Arg::new("skip-test")
+                .long("skip-test")
+                .help("Save without sending a test request")
+                .action(clap::ArgAction::SetTrue))
+            .arg(Arg::new("force")
+                .long("force")
+                .help("Replace an adapter of the same name with --non-interactive")
+                .action(clap::ArgAction::SetTrue)))
 }
@@ -1,17 +1,99 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::error::Error;
+use toml_edit::DocumentMut;
+use crate::providers::ChatMessage;
+use crate::secrets;
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Config {
+    /// Schema version; older layouts are migrated when the file is loaded.
+    #[serde(default)]
+    pub version: u32,
     pub default: DefaultConfig,
-    pub adapter: AdapterConfig,
+    /// Named adapters, written as `[adapters.<name>]` tables.
+    pub adapters: BTreeMap<String, AdapterConfig>,
+    /// Roles selectable with `--role`, written as `[roles.<name>]` tables.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub roles: BTreeMap<String, RoleConfig>,
+    /// How long conversations are fitted into the model's context window.
+    #[serde(default, skip_serializing_if = "ContextConfig::is_default")]
+    pub context: ContextConfig,
+    /// Limits on what is read from stdin along with the question.
+    #[serde(default, skip_serializing_if = "InputConfig::is_default")]
+    pub input: InputConfig,
     pub logging: LoggingConfig,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct DefaultConfig {
+    /// Name of the adapter used when `--adapter` is not given.
     pub adapter: String,
+    /// System prompt for adapters that don't set their own.
+    pub system_prompt: Option<String>,
+    /// Abilities the function calling workflow may use; all when unset.
+    pub allowed_abilities: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct RoleConfig {
+    /// One line shown by `roles list`.
+    pub description: Option<String>,
+    pub system_prompt: String,
+    /// Few-shot messages sent between the system prompt and the question.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub examples: Vec<ChatMessage>,
+    /// Adapter to use unless `--adapter` is given.
+    pub adapter: Option<String>,
+    pub model: Option<String>,
+    pub temperature: Option<f32>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct ContextConfig {
+    /// What to do when a conversation no longer fits. Defaults to `drop_oldest`.
+    pub strategy: Option<CompactionStrategy>,
+    /// Turns kept word for word by `keep_last` and `summarize`.
+    pub keep_last_turns: Option<usize>,
+    /// Context window sizes in tokens by model, overriding the built-in
+    /// table. A name ending in `*` matches every model starting with it.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub windows: BTreeMap<String, u32>,
+}
+
+impl ContextConfig {
+    fn is_default(&self) -> bool {
+        *self == ContextConfig::default()
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq)]
+pub struct InputConfig {
+    /// Bytes read from stdin before the rest is cut off. Defaults to 256 KiB.
+    pub max_stdin_bytes: Option<usize>,
+    /// Bytes read from each file attached with `--file`. Defaults to 100 KiB.
+    pub max_file_bytes: Option<usize>,
+    /// Bytes read from all attached files together. Defaults to 512 KiB.
+    pub max_total_file_bytes: Option<usize>,
+}
+
+impl InputConfig {
+    fn is_default(&self) -> bool {
+        *self == InputConfig::default()
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CompactionStrategy {
+    /// Drop the oldest turns until the conversation fits.
+    #[default]
+    DropOldest,
+    /// Keep only the last `keep_last_turns` turns.
+    KeepLast,
+    /// Have the model summarize the older turns into one message.
+    Summarize,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -20,36 +102,150 @@ pub enum AdapterConfig {
     OpenAI(OpenAIConfig),
     Claude(ClaudeConfig),
     Zhipu(ChatGLMConfig),
+    Gemini(GeminiConfig),
+    Ollama(OllamaConfig),
+    AzureOpenAI(AzureOpenAIConfig),
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct OpenAIConfig {
     pub base_url: String,
     pub default_model: String,
+    /// The API token. May reference environment variables as `${VAR}`.
+    #[serde(default)]
     pub token: String,
+    /// Run this command and use its first line of output as the token.
+    pub token_cmd: Option<String>,
+    /// Read the token from this file; warns when others can read it.
+    pub token_file: Option<String>,
     pub temperature: f32,
     pub top_p: Option<f32>,
     pub max_tokens: u32,
+    pub seed: Option<u64>,
+    pub stop: Option<Vec<String>>,
+    /// Replaces the default system prompt.
+    pub system_prompt: Option<String>,
     pub function_calling_config: Option<FunctionCallingConfig>,
+    /// Which OpenAI endpoint to talk to. Defaults to chat/completions.
+    pub wire_api: Option<WireApi>,
+    /// Ask reasoning models for a summary of their reasoning ("auto",
+    /// "concise" or "detailed"). Only used by the Responses API.
+    pub reasoning_summary: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum WireApi {
+    #[default]
+    ChatCompletions,
+    Responses,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct AzureOpenAIConfig {
+    /// The resource endpoint, e.g. `https://my-resource.openai.azure.com`.
+    pub base_url: String,
+    /// Azure routes requests by deployment name rather than by model.
+    pub deployment: String,
+    pub api_version: String,
+    /// The API token. May reference environment variables as `${VAR}`.
+    #[serde(default)]
+    pub token: String,
+    /// Run this command and use its first line of output as the token.
+    pub token_cmd: Option<String>,
+    /// Read the token from this file; warns when others can read it.
+    pub token_file: Option<String>,
+    pub temperature: f32,
+    pub top_p: Option<f32>,
+    pub max_tokens: u32,
+    pub seed: Option<u64>,
+    pub stop: Option<Vec<String>>,
+    /// Replaces the default system prompt.
+    pub system_prompt: Option<String>,
+    pub function_calling_config: Option<FunctionCallingConfig>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct ClaudeConfig {
     pub base_url: String,
     pub default_model: String,
+    /// The API token. May reference environment variables as `${VAR}`.
+    #[serde(default)]
     pub token: String,
+    /// Run this command and use its first line of output as the token.
+    pub token_cmd: Option<String>,
+    /// Read the token from this file; warns when others can read it.
+    pub token_file: Option<String>,
     pub temperature: f32,
     pub max_tokens: u32,
+    pub stop: Option<Vec<String>>,
+    /// Replaces the default system prompt.
+    pub system_prompt: Option<String>,
     pub function_calling_config: Option<FunctionCallingConfig>,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct ChatGLMConfig {
     pub base_url: String,
     pub default_model: String,
+    /// The API token. May reference environment variables as `${VAR}`.
+    #[serde(default)]
     pub token: String,
+    /// Run this command and use its first line of output as the token.
+    pub token_cmd: Option<String>,
+    /// Read the token from this file; warns when others can read it.
+    pub token_file: Option<String>,
     pub temperature: f32,
     pub max_tokens: u32,
+    pub stop: Option<Vec<String>>,
+    /// Replaces the default system prompt.
+    pub system_prompt: Option<String>,
+    pub function_calling_config: Option<FunctionCallingConfig>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct GeminiConfig {
+    pub base_url: String,
+    pub default_model: String,
+    /// The API token. May reference environment variables as `${VAR}`.
+    #[serde(default)]
+    pub token: String,
+    /// Run this command and use its first line of output as the token.
+    pub token_cmd: Option<String>,
+    /// Read the token from this file; warns when others can read it.
+    pub token_file: Option<String>,
+    pub temperature: f32,
+    pub top_p: Option<f32>,
+    pub max_tokens: u32,
+    pub seed: Option<u64>,
+    pub stop: Option<Vec<String>>,
+    /// Replaces the default system prompt.
+    pub system_prompt: Option<String>,
+    pub function_calling_config: Option<FunctionCallingConfig>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct OllamaConfig {
+    pub base_url: String,
+    pub default_model: String,
+    /// Only needed when Ollama sits behind an authenticating proxy.
+    pub token: Option<String>,
+    /// Run this command and use its first line of output as the token.
+    pub token_cmd: Option<String>,
+    /// Read the token from this file; warns when others can read it.
+    pub token_file: Option<String>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub max_tokens: Option<u32>,
+    pub num_ctx: Option<u32>,
+    /// How long the model stays loaded after the request, e.g. "5m".
+    pub keep_alive: Option<String>,
+    /// Set to "json" to force a JSON answer.
+    pub format: Option<String>,
+    pub seed: Option<u64>,
+    pub stop: Option<Vec<String>>,
+    /// Replaces the default system prompt.
+    pub system_prompt: Option<String>,
     pub function_calling_config: Option<FunctionCallingConfig>,
 }
 
@@ -65,15 +261,161 @@ pub struct FunctionCallingConfig {
     pub function_declaration_names: Vec<String>,
 }
 
+impl AdapterConfig {
+    /// The adapter's `type`, as written in the config file.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AdapterConfig::OpenAI(_) => "OpenAI",
+            AdapterConfig::Claude(_) => "Claude",
+            AdapterConfig::Zhipu(_) => "Zhipu",
+            AdapterConfig::Gemini(_) => "Gemini",
+            AdapterConfig::Ollama(_) => "Ollama",
+            AdapterConfig::AzureOpenAI(_) => "AzureOpenAI",
+        }
+    }
+
+    /// The model requests go to; the deployment name for Azure.
+    pub fn model(&self) -> &str {
+        match self {
+            AdapterConfig::OpenAI(config) => &config.default_model,
+            AdapterConfig::Claude(config) => &config.default_model,
+            AdapterConfig::Zhipu(config) => &config.default_model,
+            AdapterConfig::Gemini(config) => &config.default_model,
+            AdapterConfig::Ollama(config) => &config.default_model,
+            AdapterConfig::AzureOpenAI(config) => &config.deployment,
+        }
+    }
+
+    /// Tokens reserved for the answer; `None` when the provider decides.
+    pub fn max_tokens(&self) -> Option<u32> {
+        match self {
+            AdapterConfig::OpenAI(config) => Some(config.max_tokens),
+            AdapterConfig::Claude(config) => Some(config.max_tokens),
+            AdapterConfig::Zhipu(config) => Some(config.max_tokens),
+            AdapterConfig::Gemini(config) => Some(config.max_tokens),
+            AdapterConfig::Ollama(config) => config.max_tokens,
+            AdapterConfig::AzureOpenAI(config) => Some(config.max_tokens),
+        }
+    }
+
+    pub fn system_prompt(&self) -> Option<&str> {
+        match self {
+            AdapterConfig::OpenAI(config) => config.system_prompt.as_deref(),
+            AdapterConfig::Claude(config) => config.system_prompt.as_deref(),
+            AdapterConfig::Zhipu(config) => config.system_prompt.as_deref(),
+            AdapterConfig::Gemini(config) => config.system_prompt.as_deref(),
+            AdapterConfig::Ollama(config) => config.system_prompt.as_deref(),
+            AdapterConfig::AzureOpenAI(config) => config.system_prompt.as_deref(),
+        }
+    }
+
+    /// Replaces the token reference (`${VAR}`, `token_cmd` or `token_file`)
+    /// with the secret itself. Call this on the copy handed to the client,
+    /// never on a config that may be saved.
+    pub fn resolve_token(&mut self) -> Result<(), Box<dyn Error>> {
+        let (token, token_cmd, token_file) = match self {
+            AdapterConfig::OpenAI(config) => (&mut config.token, &mut config.token_cmd, &mut config.token_file),
+            AdapterConfig::Claude(config) => (&mut config.token, &mut config.token_cmd, &mut config.token_file),
+            AdapterConfig::Zhipu(config) => (&mut config.token, &mut config.token_cmd, &mut config.token_file),
+            AdapterConfig::Gemini(config) => (&mut config.token, &mut config.token_cmd, &mut config.token_file),
+            AdapterConfig::AzureOpenAI(config) => (&mut config.token, &mut config.token_cmd, &mut config.token_file),
+            AdapterConfig::Ollama(config) => {
+                // Ollama runs without a token unless one is configured
+                if config.token.is_none() && config.token_cmd.is_none() && config.token_file.is_none() {
+                    return Ok(());
+                }
+                let token = config.token.take().unwrap_or_default();
+                let resolved = secrets::resolve_token(&token, config.token_cmd.take().as_deref(), config.token_file.take().as_deref())?;
+                config.token = Some(resolved);
+                return Ok(());
+            },
+        };
+        *token = secrets::resolve_token(token, token_cmd.take().as_deref(), token_file.take().as_deref())?;
+        Ok(())
+    }
+}
+
 impl Config {
+    /// Loads a single file for tests, migrating older layouts in memory. The
+    /// program itself goes through `LayeredConfig`, which merges every layer.
+    #[cfg(test)]
     pub fn from_file(path: &str) -> Result<Self, Box<dyn Error>> {
         let content: String = fs::read_to_string(path)?;
-        let config: Config = toml::from_str(&content)?;
+        let mut doc: DocumentMut = content.parse()?;
+        crate::config_migration::migrate(&mut doc);
+        let config: Config = toml::from_str(&doc.to_string())?;
         Ok(config)
     }
+    /// Writes the config back as-is; token references are kept, never the
+    /// resolved secrets.
     pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
-        let content = toml::to_string(self)?;
+        let content = to_toml_string(self)?;
         fs::write(path, content)?;
         Ok(())
     }
 }
+
+/// Serializes config values as TOML. Settings are `f32`, which would
+/// otherwise be written widened, as `0.699999988079071` for `0.7`.
+pub fn to_toml_string<T: Serialize>(value: &T) -> Result<String, Box<dyn Error>> {
+    let mut doc: DocumentMut = toml::to_string(value)?.parse()?;
+    shorten_floats(doc.as_item_mut());
+    Ok(doc.to_string())
+}
+
+fn shorten_floats(item: &mut toml_edit::Item) {
+    match item {
+        toml_edit::Item::Value(value) => shorten_float_value(value),
+        toml_edit::Item::Table(table) => table.iter_mut().for_each(|(_, item)| shorten_floats(item)),
+        toml_edit::Item::ArrayOfTables(tables) => tables.iter_mut()
+            .for_each(|table| table.iter_mut().for_each(|(_, item)| shorten_floats(item))),
+        toml_edit::Item::None => {},
+    }
+}
+
+fn shorten_float_value(value: &mut toml_edit::Value) {
+    match value {
+        toml_edit::Value::Float(float) => {
+            let short: f64 = (*float.value() as f32).to_string().parse().unwrap_or(*float.value());
+            let decor = float.decor().clone();
+            *float = toml_edit::Formatted::new(short);
+            *float.decor_mut() = decor;
+        },
+        toml_edit::Value::Array(array) => array.iter_mut().for_each(shorten_float_value),
+        toml_edit::Value::InlineTable(table) => table.iter_mut().for_each(|(_, value)| shorten_float_value(value)),
+        _ => {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_example_config_parses() {
+        let config = Config::from_file("config.toml.example").expect("config.toml.example should parse");
+        assert_eq!(config.default.adapter, "openai_adapter");
+        assert!(matches!(config.adapters["openai_adapter"], AdapterConfig::OpenAI(_)));
+        assert!(matches!(config.adapters["claude_adapter"], AdapterConfig::Claude(_)));
+    }
+
+    #[test]
+    fn test_save_keeps_token_reference() {
+        std::env::set_var("RUSKGPT_TEST_SAVE_KEY", "sk-secret");
+        let mut config = Config::from_file("config.toml.example").unwrap();
+        let AdapterConfig::OpenAI(openai) = config.adapters.get_mut("openai_adapter").unwrap() else { unreachable!() };
+        openai.token = "${RUSKGPT_TEST_SAVE_KEY}".to_string();
+
+        let mut resolved = config.adapters["openai_adapter"].clone();
+        resolved.resolve_token().unwrap();
+        let AdapterConfig::OpenAI(resolved) = resolved else { unreachable!() };
+        assert_eq!(resolved.token, "sk-secret");
+
+        let path = std::env::temp_dir().join(format!("ruskgpt-save-{}.toml", std::process::id()));
+        config.save(path.to_str().unwrap()).unwrap();
+        let saved = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(saved.contains("token = \"${RUSKGPT_TEST_SAVE_KEY}\""));
+        assert!(!saved.contains("sk-secret"));
+    }
+}
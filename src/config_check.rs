@@ -0,0 +1,411 @@
+//! Diagnostics for `ruskgpt config check`: TOML and schema errors with
+//! line and column, unknown keys, and values no provider would accept.
+
+use std::fmt;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use toml::Table;
+use toml_edit::{DocumentMut, ImDocument};
+use crate::config::{AdapterConfig, AzureOpenAIConfig, ChatGLMConfig, ClaudeConfig, Config, GeminiConfig, OllamaConfig, OpenAIConfig};
+use crate::config_migration;
+use crate::overrides;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// 1-based line and column, when the problem can be pinned down.
+    pub location: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+impl Diagnostic {
+    /// Formats the diagnostic as `path:line:column: severity: message`.
+    pub fn display(&self, path: &Path) -> String {
+        match self.location {
+            Some((line, column)) => format!("{}:{}:{}: {}: {}", path.display(), line, column, self.severity, self.message),
+            None => format!("{}: {}: {}", path.display(), self.severity, self.message),
+        }
+    }
+}
+
+/// Checks one config file. Syntax and unknown keys are checked in the file
+/// itself; required fields and values are checked in `merged`, the config
+/// with every layer applied, so a file may leave whole sections to another
+/// layer. Without `merged` the file must be complete on its own.
+pub fn check_file(path: &Path, merged: Option<&Table>) -> Vec<Diagnostic> {
+    match fs::read_to_string(path) {
+        Ok(content) => check_str(&content, merged),
+        Err(e) => vec![Diagnostic {
+            severity: Severity::Error,
+            location: None,
+            message: format!("cannot read file: {}", e),
+        }],
+    }
+}
+
+/// The TOML syntax error in a file, if there is one.
+pub fn syntax_error(content: &str) -> Option<Diagnostic> {
+    let e: toml_edit::TomlError = content.parse::<DocumentMut>().err()?;
+    Some(error_at(content, e.span(), e.message().trim()))
+}
+
+pub fn check_str(content: &str, merged: Option<&Table>) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    let mut doc: DocumentMut = match content.parse() {
+        Ok(doc) => doc,
+        Err(_) => {
+            diagnostics.extend(syntax_error(content));
+            return diagnostics;
+        }
+    };
+
+    // Line numbers below refer to the migrated layout when a migration applies
+    let notes = config_migration::migrate(&mut doc);
+    let migrated = doc.to_string();
+    let text = if notes.is_empty() { content } else { migrated.as_str() };
+    if !notes.is_empty() {
+        diagnostics.push(Diagnostic {
+            severity: Severity::Warning,
+            location: None,
+            message: format!(
+                "uses an older layout and will be migrated to version {} on next load: {}",
+                config_migration::CURRENT_CONFIG_VERSION,
+                notes.join("; ")
+            ),
+        });
+    }
+
+    let result = match merged {
+        Some(merged) => toml::Value::Table(merged.clone()).try_into::<Config>(),
+        None => toml::from_str::<Config>(text),
+    };
+    let config = match result {
+        Ok(config) => config,
+        Err(e) => {
+            // Point into this file when it has the same problem on its own
+            let diagnostic = match toml::from_str::<Config>(text) {
+                Err(own) if own.message() == e.message() => own.span()
+                    .and_then(|span| adapter_error(text, line_column(text, span.start).0))
+                    .or_else(|| (1..=text.lines().count()).find_map(|line| adapter_error(text, line)))
+                    .unwrap_or_else(|| error_at(text, own.span(), own.message().trim())),
+                _ => error_at(text, None, e.message().trim()),
+            };
+            diagnostics.push(diagnostic);
+            return diagnostics;
+        }
+    };
+
+    let spans = ImDocument::parse(text.to_string()).ok();
+    let locate = |path: &[&str]| spans.as_ref().and_then(|doc| key_span(doc, path)).map(|span| line_column(text, span.start));
+
+    // Every key that deserialized is written back out; whatever is missing
+    // from the round trip was ignored
+    if let (Ok(raw), Ok(known)) = (toml::from_str::<toml::Value>(text), toml::Value::try_from(&config)) {
+        let mut unknown = Vec::new();
+        unknown_keys(&raw, &known, &mut Vec::new(), &mut unknown);
+        for path in unknown {
+            let parts: Vec<&str> = path.iter().map(String::as_str).collect();
+            diagnostics.push(Diagnostic {
+                severity: Severity::Warning,
+                location: locate(&parts),
+                message: format!("unknown key `{}`", path.join(".")),
+            });
+        }
+    }
+
+    if !config.adapters.contains_key(&config.default.adapter) {
+        let valid = config.adapters.keys().cloned().collect::<Vec<_>>().join(", ");
+        diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            location: locate(&["default", "adapter"]),
+            message: format!("default.adapter `{}` is not defined; valid adapters: {}", config.default.adapter, valid),
+        });
+    }
+
+    for (name, adapter) in &config.adapters {
+        for (key, severity, message) in semantic_problems(adapter) {
+            diagnostics.push(Diagnostic {
+                severity,
+                location: locate(&["adapters", name, key]).or_else(|| locate(&["adapters", name])),
+                message: format!("adapters.{}.{}: {}", name, key, message),
+            });
+        }
+    }
+
+    if config.context.keep_last_turns == Some(0) {
+        diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            location: locate(&["context", "keep_last_turns"]),
+            message: "context.keep_last_turns: must be at least 1".to_string(),
+        });
+    }
+    for (model, &window) in &config.context.windows {
+        if window == 0 {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                location: locate(&["context", "windows", model]),
+                message: format!("context.windows.{}: must be at least 1", model),
+            });
+        }
+    }
+    let limits = [
+        ("max_stdin_bytes", config.input.max_stdin_bytes),
+        ("max_file_bytes", config.input.max_file_bytes),
+        ("max_total_file_bytes", config.input.max_total_file_bytes),
+    ];
+    for (key, _) in limits.iter().filter(|(_, limit)| *limit == Some(0)) {
+        diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            location: locate(&["input", key]),
+            message: format!("input.{}: must be at least 1", key),
+        });
+    }
+
+    diagnostics
+}
+
+/// Values that parse fine but that the provider would reject.
+fn semantic_problems(adapter: &AdapterConfig) -> Vec<(&'static str, Severity, String)> {
+    let mut problems = Vec::new();
+
+    let (base_url, token, temperature, top_p, max_tokens) = match adapter {
+        AdapterConfig::OpenAI(c) => (&c.base_url, Some((&c.token, &c.token_cmd, &c.token_file)), Some(c.temperature), c.top_p, Some(c.max_tokens)),
+        AdapterConfig::AzureOpenAI(c) => (&c.base_url, Some((&c.token, &c.token_cmd, &c.token_file)), Some(c.temperature), c.top_p, Some(c.max_tokens)),
+        AdapterConfig::Claude(c) => (&c.base_url, Some((&c.token, &c.token_cmd, &c.token_file)), Some(c.temperature), None, Some(c.max_tokens)),
+        AdapterConfig::Zhipu(c) => (&c.base_url, Some((&c.token, &c.token_cmd, &c.token_file)), Some(c.temperature), None, Some(c.max_tokens)),
+        AdapterConfig::Gemini(c) => (&c.base_url, Some((&c.token, &c.token_cmd, &c.token_file)), Some(c.temperature), c.top_p, Some(c.max_tokens)),
+        AdapterConfig::Ollama(c) => (&c.base_url, None, c.temperature, c.top_p, c.max_tokens),
+    };
+
+    match reqwest::Url::parse(base_url) {
+        Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {},
+        _ => problems.push(("base_url", Severity::Error, format!("`{}` is not an http(s) URL", base_url))),
+    }
+
+    if let Some((token, token_cmd, token_file)) = token {
+        if token.trim().is_empty() && token_cmd.is_none() && token_file.is_none() {
+            problems.push(("token", Severity::Error, "is empty; set token, token_cmd or token_file".to_string()));
+        }
+    }
+
+    if let Some(temperature) = temperature {
+        if let Some(max) = overrides::max_temperature(adapter) {
+            if !(0.0..=max).contains(&temperature) {
+                problems.push(("temperature", Severity::Error, format!("{} is out of range; {} accepts 0 to {}", temperature, adapter.kind(), max)));
+            }
+        }
+    }
+    if let Some(top_p) = top_p {
+        if !(0.0..=1.0).contains(&top_p) {
+            problems.push(("top_p", Severity::Error, format!("{} is out of range; expected 0 to 1", top_p)));
+        }
+    }
+    if max_tokens == Some(0) {
+        problems.push(("max_tokens", Severity::Error, "must be at least 1".to_string()));
+    }
+
+    problems
+}
+
+/// Adapters are an internally tagged enum, so serde reports their errors at
+/// the table header. Deserializing the table's body on its own, at the same
+/// line offsets, points at the offending key instead. `header` is the
+/// 1-based line of the table header.
+fn adapter_error(text: &str, header: usize) -> Option<Diagnostic> {
+    let lines: Vec<&str> = text.lines().collect();
+    if !lines.get(header - 1)?.trim_start().starts_with("[adapters.") {
+        return None;
+    }
+
+    let body_len = lines[header..].iter().take_while(|line| !line.trim_start().starts_with('[')).count();
+    let body = "\n".repeat(header) + &lines[header..header + body_len].join("\n");
+    let kind = toml::from_str::<toml::Table>(&body).ok()?.get("type")?.as_str()?.to_string();
+    let result = match kind.as_str() {
+        "OpenAI" => toml::from_str::<OpenAIConfig>(&body).map(drop),
+        "Claude" => toml::from_str::<ClaudeConfig>(&body).map(drop),
+        "Zhipu" => toml::from_str::<ChatGLMConfig>(&body).map(drop),
+        "Gemini" => toml::from_str::<GeminiConfig>(&body).map(drop),
+        "Ollama" => toml::from_str::<OllamaConfig>(&body).map(drop),
+        "AzureOpenAI" => toml::from_str::<AzureOpenAIConfig>(&body).map(drop),
+        _ => return None,
+    };
+    let e = result.err()?;
+    let mut diagnostic = error_at(&body, e.span(), e.message().trim());
+    if diagnostic.location.is_none_or(|(line, _)| line <= header) {
+        // Missing fields belong to the table as a whole
+        diagnostic.location = Some((header, 1));
+        diagnostic.message = format!("{} in [{}]", diagnostic.message, lines[header - 1].trim().trim_matches(|c| c == '[' || c == ']'));
+    }
+    Some(diagnostic)
+}
+
+fn unknown_keys(raw: &toml::Value, known: &toml::Value, path: &mut Vec<String>, unknown: &mut Vec<Vec<String>>) {
+    let (Some(raw), Some(known)) = (raw.as_table(), known.as_table()) else {
+        return;
+    };
+    for (key, value) in raw {
+        path.push(key.clone());
+        match known.get(key) {
+            Some(known_value) => unknown_keys(value, known_value, path, unknown),
+            None => unknown.push(path.clone()),
+        }
+        path.pop();
+    }
+}
+
+fn key_span(doc: &ImDocument<String>, path: &[&str]) -> Option<Range<usize>> {
+    let (last, parents) = path.split_last()?;
+    let mut table = doc.as_table() as &dyn toml_edit::TableLike;
+    for part in parents {
+        table = table.get(part)?.as_table_like()?;
+    }
+    let (key, item) = table.get_key_value(last)?;
+    key.span().or_else(|| item.span())
+}
+
+fn error_at(text: &str, span: Option<Range<usize>>, message: &str) -> Diagnostic {
+    Diagnostic {
+        severity: Severity::Error,
+        location: span.map(|span| line_column(text, span.start)),
+        message: message.to_string(),
+    }
+}
+
+/// Converts a byte offset into a 1-based line and column.
+fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VALID: &str = r#"version = 2
+
+[default]
+adapter = "openai_adapter"
+
+[adapters.openai_adapter]
+type = "OpenAI"
+base_url = "https://api.openai.com/v1"
+default_model = "gpt-4o"
+token = "${OPENAI_API_KEY}"
+temperature = 0.7
+max_tokens = 2048
+
+[logging]
+level = "info"
+"#;
+
+    #[test]
+    fn test_valid_config() {
+        assert_eq!(check_str(VALID, None), vec![]);
+    }
+
+    #[test]
+    fn test_syntax_error_location() {
+        let diagnostics = check_str(&VALID.replace("temperature = 0.7", "temperature = = 0.7"), None);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(diagnostics[0].location, Some((11, 15)));
+    }
+
+    #[test]
+    fn test_type_error_location() {
+        let diagnostics = check_str(&VALID.replace("max_tokens = 2048", "max_tokens = \"lots\""), None);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].location, Some((12, 14)));
+        assert!(diagnostics[0].message.contains("invalid type: string \"lots\""), "{}", diagnostics[0].message);
+    }
+
+    #[test]
+    fn test_missing_field_location() {
+        let diagnostics = check_str(&VALID.replace("default_model = \"gpt-4o\"\n", ""), None);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].location, Some((6, 1)));
+        assert_eq!(diagnostics[0].message, "missing field `default_model` in [adapters.openai_adapter]");
+    }
+
+    #[test]
+    fn test_unknown_keys_and_semantics() {
+        let content = VALID
+            .replace("temperature = 0.7", "temprature = 0.7\ntemperature = 3.5")
+            .replace("token = \"${OPENAI_API_KEY}\"", "token = \"\"")
+            .replace("https://api.openai.com/v1", "api.openai.com");
+        let messages: Vec<String> = check_str(&content, None).iter()
+            .map(|d| format!("{:?} {:?} {}", d.severity, d.location, d.message))
+            .collect();
+        assert_eq!(messages, vec![
+            "Warning Some((11, 1)) unknown key `adapters.openai_adapter.temprature`",
+            "Error Some((8, 1)) adapters.openai_adapter.base_url: `api.openai.com` is not an http(s) URL",
+            "Error Some((10, 1)) adapters.openai_adapter.token: is empty; set token, token_cmd or token_file",
+            "Error Some((12, 1)) adapters.openai_adapter.temperature: 3.5 is out of range; OpenAI accepts 0 to 2",
+        ]);
+    }
+
+    #[test]
+    fn test_context_section() {
+        let content = format!("{}\n[context]\nstrategy = \"summarize\"\nkeep_last_turns = 0\n\n[context.windows]\n\"gpt-4o*\" = 0\n", VALID);
+        let messages: Vec<String> = check_str(&content, None).iter().map(|d| d.message.clone()).collect();
+        assert_eq!(messages, vec![
+            "context.keep_last_turns: must be at least 1",
+            "context.windows.gpt-4o*: must be at least 1",
+        ]);
+        let diagnostics = check_str(&content.replace("\"summarize\"", "\"compress\""), None);
+        assert!(diagnostics[0].message.contains("unknown variant `compress`"), "{}", diagnostics[0].message);
+    }
+
+    #[test]
+    fn test_partial_file_with_system_layer() {
+        use crate::config_layers::{Layer, LayeredConfig, Origin};
+
+        let user = VALID.replace("[logging]\nlevel = \"info\"\n", "");
+        let messages: Vec<String> = check_str(&user, None).iter().map(|d| d.message.clone()).collect();
+        assert_eq!(messages, vec!["missing field `logging`"]);
+
+        let mut layered = LayeredConfig::default();
+        layered.merge(toml::from_str("[logging]\nlevel = \"warn\"\n").unwrap(), Origin { layer: Layer::System, path: None });
+        layered.merge(toml::from_str(&user).unwrap(), Origin { layer: Layer::User, path: None });
+        assert_eq!(check_str(&user, Some(&layered.merged)), vec![]);
+
+        // Values are still checked, and located when they are in this file
+        let user = user.replace("temperature = 0.7", "temperature = 3.5\ntemprature = 0.7");
+        layered.merge(toml::from_str(&user).unwrap(), Origin { layer: Layer::User, path: None });
+        let messages: Vec<String> = check_str(&user, Some(&layered.merged)).iter()
+            .map(|d| format!("{:?} {:?} {}", d.severity, d.location, d.message))
+            .collect();
+        assert_eq!(messages, vec![
+            "Warning Some((12, 1)) unknown key `adapters.openai_adapter.temprature`",
+            "Error Some((11, 1)) adapters.openai_adapter.temperature: 3.5 is out of range; OpenAI accepts 0 to 2",
+        ]);
+    }
+
+    #[test]
+    fn test_old_layout_is_flagged() {
+        let content = VALID.replace("version = 2\n", "").replace("[adapters.openai_adapter]", "[adapter]");
+        let diagnostics = check_str(&content, None);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert!(diagnostics[0].message.contains("moved [adapter] to [adapters.openai_adapter]"));
+    }
+}
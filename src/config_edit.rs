@@ -0,0 +1,220 @@
+//! `ruskgpt config get/set/unset`: edits single keys addressed by dotted
+//! paths such as `adapters.work.default_model`, leaving comments and key
+//! order of the rest of the file untouched.
+
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use toml_edit::{DocumentMut, Item, Table, TableLike, Value};
+use crate::config_migration;
+
+/// Prints the value at `path`: strings bare, everything else as TOML.
+pub fn get(doc: &DocumentMut, path: &str) -> Result<String, Box<dyn Error>> {
+    let parts = split_path(path)?;
+    let mut item = doc.as_item();
+    for part in &parts {
+        item = item.get(part).ok_or_else(|| format!("Key `{}` is not set", path))?;
+    }
+
+    Ok(match item {
+        Item::Value(Value::String(s)) => s.value().clone(),
+        Item::Value(value) => value.to_string().trim().to_string(),
+        Item::Table(table) => {
+            let mut doc = DocumentMut::new();
+            for (key, item) in table.iter() {
+                doc.insert(key, item.clone());
+            }
+            doc.to_string().trim_end().to_string()
+        },
+        _ => item.to_string().trim().to_string(),
+    })
+}
+
+/// Sets `path` to `raw`. The value is read as TOML (`0.5`, `true`,
+/// `["a", "b"]`) and falls back to a plain string; an existing string key
+/// stays a string. Missing tables are created along the way.
+pub fn set(doc: &mut DocumentMut, path: &str, raw: &str) -> Result<(), Box<dyn Error>> {
+    let parts = split_path(path)?;
+    let (last, parents) = parts.split_last().unwrap();
+
+    let mut table: &mut dyn TableLike = doc.as_table_mut();
+    for (i, part) in parents.iter().enumerate() {
+        let item = table.entry(part).or_insert_with(|| {
+            let mut new_table = Table::new();
+            // Only the innermost table needs a header of its own
+            new_table.set_implicit(i + 1 < parents.len());
+            Item::Table(new_table)
+        });
+        table = item.as_table_like_mut()
+            .ok_or_else(|| format!("Cannot set `{}`: `{}` is not a table", path, parts[..=i].join(".")))?;
+    }
+
+    let existing = table.get(last);
+    if existing.is_some_and(|item| item.is_table_like()) {
+        return Err(format!("Cannot set `{}`: it is a table; set one of its keys instead", path).into());
+    }
+
+    let mut value = parse_value(raw);
+    if existing.is_some_and(Item::is_str) && !value.is_str() {
+        value = Value::from(raw);
+    }
+    if let Some(Item::Value(old)) = existing {
+        // Keep the spacing and any trailing comment of the old value
+        *value.decor_mut() = old.decor().clone();
+    }
+    table.insert(last, Item::Value(value));
+    Ok(())
+}
+
+/// Removes the key at `path`.
+pub fn unset(doc: &mut DocumentMut, path: &str) -> Result<(), Box<dyn Error>> {
+    let parts = split_path(path)?;
+    let (last, parents) = parts.split_last().unwrap();
+
+    let mut table: &mut dyn TableLike = doc.as_table_mut();
+    for part in parents {
+        table = table.get_mut(part)
+            .and_then(Item::as_table_like_mut)
+            .ok_or_else(|| format!("Key `{}` is not set", path))?;
+    }
+    table.remove(last).ok_or_else(|| format!("Key `{}` is not set", path))?;
+    Ok(())
+}
+
+/// Applies `edit` to the config file and writes it back. A file with an
+/// older layout is migrated first, with a backup. The result is not
+/// validated here, so a new adapter can be built up one key at a time.
+pub fn edit_file<F>(path: &Path, edit: F) -> Result<(), Box<dyn Error>>
+where
+    F: FnOnce(&mut DocumentMut) -> Result<(), Box<dyn Error>>,
+{
+    config_migration::migrate_file_with_notice(path)?;
+    let mut doc: DocumentMut = fs::read_to_string(path)?.parse()?;
+    edit(&mut doc)?;
+    write_file(path, &doc.to_string())
+}
+
+/// Writes to a temporary file next to `path` and renames it over the
+/// original, so a failed write leaves the old file intact. The original's
+/// permissions are kept.
+pub fn write_file(path: &Path, content: &str) -> Result<(), Box<dyn Error>> {
+    let file_name = path.file_name().ok_or_else(|| format!("{} is not a file", path.display()))?;
+    let temp_path = path.with_file_name(format!(".{}.{}.tmp", file_name.to_string_lossy(), std::process::id()));
+    let result = (|| -> Result<(), Box<dyn Error>> {
+        let mut file = File::create(&temp_path)?;
+        file.write_all(content.as_bytes())?;
+        file.sync_all()?;
+        if let Ok(metadata) = fs::metadata(path) {
+            fs::set_permissions(&temp_path, metadata.permissions())?;
+        }
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+pub fn read_file(path: &Path) -> Result<DocumentMut, Box<dyn Error>> {
+    let mut doc: DocumentMut = fs::read_to_string(path)?.parse()?;
+    config_migration::migrate(&mut doc);
+    Ok(doc)
+}
+
+fn split_path(path: &str) -> Result<Vec<&str>, Box<dyn Error>> {
+    let parts: Vec<&str> = path.split('.').collect();
+    if parts.iter().any(|part| part.is_empty()) {
+        return Err(format!("Invalid key `{}`; expected a dotted path like adapters.work.default_model", path).into());
+    }
+    Ok(parts)
+}
+
+fn parse_value(raw: &str) -> Value {
+    match raw.parse::<Value>() {
+        Ok(value) => value.decorated(" ", ""),
+        Err(_) => Value::from(raw),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"version = 2
+
+[default]
+adapter = "openai_adapter" # used when --adapter is not given
+
+# Personal account
+[adapters.openai_adapter]
+type = "OpenAI"
+base_url = "https://api.openai.com/v1"
+default_model = "gpt-4o"
+token = "${OPENAI_API_KEY}"
+temperature = 0.7
+max_tokens = 2048
+
+[logging]
+level = "info"
+"#;
+
+    #[test]
+    fn test_set_preserves_formatting() {
+        let mut doc: DocumentMut = CONFIG.parse().unwrap();
+        set(&mut doc, "default.adapter", "work").unwrap();
+        set(&mut doc, "adapters.openai_adapter.temperature", "0.2").unwrap();
+        set(&mut doc, "adapters.openai_adapter.stop", r#"["END"]"#).unwrap();
+        set(&mut doc, "adapters.work.default_model", "gpt-4o-mini").unwrap();
+
+        let expected = CONFIG
+            .replace(r#"adapter = "openai_adapter" #"#, r#"adapter = "work" #"#)
+            .replace("temperature = 0.7", "temperature = 0.2")
+            .replace("max_tokens = 2048\n", "max_tokens = 2048\nstop = [\"END\"]\n\n[adapters.work]\ndefault_model = \"gpt-4o-mini\"\n");
+        assert_eq!(doc.to_string(), expected);
+    }
+
+    #[test]
+    fn test_get_and_unset() {
+        let mut doc: DocumentMut = CONFIG.parse().unwrap();
+        assert_eq!(get(&doc, "adapters.openai_adapter.default_model").unwrap(), "gpt-4o");
+        assert_eq!(get(&doc, "adapters.openai_adapter.max_tokens").unwrap(), "2048");
+        assert_eq!(get(&doc, "logging").unwrap(), "level = \"info\"");
+
+        unset(&mut doc, "adapters.openai_adapter.temperature").unwrap();
+        assert!(!doc.to_string().contains("temperature"));
+        assert_eq!(
+            get(&doc, "adapters.openai_adapter.temperature").unwrap_err().to_string(),
+            "Key `adapters.openai_adapter.temperature` is not set"
+        );
+        assert!(unset(&mut doc, "adapters.missing.token").is_err());
+    }
+
+    #[test]
+    fn test_edit_file_migrates_with_backup() {
+        let dir = std::env::temp_dir().join(format!("ruskgpt-edit-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        let old = CONFIG.replace("version = 2\n\n", "").replace("[adapters.openai_adapter]", "[adapter]");
+        fs::write(&path, &old).unwrap();
+
+        edit_file(&path, |doc| set(doc, "adapters.openai_adapter.temperature", "0.2")).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("config.toml.bak")).unwrap(), old);
+        let doc = read_file(&path).unwrap();
+        assert_eq!(doc["version"].as_integer(), Some(2));
+        assert_eq!(doc["adapters"]["openai_adapter"]["temperature"].as_float(), Some(0.2));
+        // No temporary file is left behind
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_set_keeps_string_type() {
+        let mut doc: DocumentMut = CONFIG.parse().unwrap();
+        set(&mut doc, "adapters.openai_adapter.default_model", "4").unwrap();
+        assert_eq!(doc["adapters"]["openai_adapter"]["default_model"].as_str(), Some("4"));
+        assert!(set(&mut doc, "adapters.openai_adapter", "x").is_err());
+        assert!(set(&mut doc, "adapters..token", "x").is_err());
+    }
+}
@@ -1,98 +1,460 @@
 use clap::ArgMatches;
+use std::collections::BTreeMap;
+use std::error::Error;
 use std::fs;
-use std::path::PathBuf;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
 use std::process::Command as ProcessCommand;
-use dirs_next::home_dir;
-use crate::config::{AdapterConfig, ChatGLMConfig, ClaudeConfig, Config, DefaultConfig, FunctionCallingConfig, LoggingConfig, OpenAIConfig};
+use log::warn;
+use crate::config::{AdapterConfig, Config, ContextConfig, DefaultConfig, FunctionCallingConfig, InputConfig, LoggingConfig, OpenAIConfig, RoleConfig};
+use crate::config_check::{self, Diagnostic, Severity};
+use crate::config_edit;
+use crate::config_layers::LayeredConfig;
+use crate::config_migration::{self, CURRENT_CONFIG_VERSION};
+use crate::overrides::GenerationOverrides;
+use crate::paths::Paths;
 
+/// The user config file: `--config`, `$XDG_CONFIG_HOME/ruskgpt/config.toml`
+/// (moved there from `~/.ruskgpt` if needed), or a `config.toml` in the
+/// current directory. The returned file may not exist yet on first run.
 pub fn get_config_file_path(matches: &ArgMatches) -> PathBuf {
+    find_config_file(matches, true)
+}
+
+/// The user config file as `get_config_file_path` would find it, but leaving
+/// a config in `~/.ruskgpt` where it is.
+pub fn peek_config_file_path(matches: &ArgMatches) -> PathBuf {
+    find_config_file(matches, false)
+}
+
+fn find_config_file(matches: &ArgMatches, migrate: bool) -> PathBuf {
     // Check if --config is specified and use it if present
     if let Some(config_path) = matches.get_one::<String>("config") {
         return PathBuf::from(config_path);
     }
 
-    let home_config_dir = home_dir().unwrap().join(".ruskgpt");
-    let home_config_path = home_config_dir.join("config.toml");
+    let paths = Paths::new();
+    let user_config_path = paths.config_file();
+
+    // Check in the config directory
+    if user_config_path.exists() {
+        return user_config_path;
+    }
 
-    // Check in home directory
-    if home_config_path.exists() {
-        return home_config_path;
+    // Check in ~/.ruskgpt
+    let legacy = if migrate { paths.migrate_legacy_config() } else { paths.legacy_config() };
+    if let Some(path) = legacy {
+        return path;
     }
 
     // Check in current directory
     let current_config_path = PathBuf::from("config.toml");
     if current_config_path.exists() {
-        // Attempt to load the config to ensure it's valid
-        if Config::from_file(current_config_path.to_str().unwrap()).is_ok() {
-            return current_config_path;
-        } else {
-            println!("Found an invalid config file in the current directory. Creating a default config.");
-        }
+        // An invalid file is reported when it is loaded rather than replaced
+        return current_config_path;
     }
 
-    // Create default config if not found or invalid
-    if !home_config_dir.exists() {
-        fs::create_dir_all(&home_config_dir).expect("Failed to create config directory");
+    user_config_path
+}
+
+/// Writes a starter config that reads the OpenAI key from the environment.
+pub fn create_default_config(config_file_path: &Path) {
+    if let Some(config_dir) = config_file_path.parent().filter(|dir| !dir.exists()) {
+        fs::create_dir_all(config_dir).expect("Failed to create config directory");
     }
 
+    let mut adapters = BTreeMap::new();
+    adapters.insert("openai_adapter".to_string(), AdapterConfig::OpenAI(OpenAIConfig {
+        base_url: "https://api.openai.com/v1".to_string(),
+        default_model: "gpt-4o-mini".to_string(),
+        token: "${OPENAI_API_KEY}".to_string(),
+        temperature: 0.7,
+        top_p: Some(1.0),
+        max_tokens: 2048,
+        function_calling_config: Some(FunctionCallingConfig {
+            mode: "ANY".to_string(),
+            allowed_function_names: None,
+            function_declaration_names: vec!["example_function".to_string()],
+        }),
+        ..Default::default()
+    }));
+
     let default_config = Config {
+        version: CURRENT_CONFIG_VERSION,
         default: DefaultConfig {
             adapter: "openai_adapter".to_string(),
+            ..Default::default()
         },
-        adapter: AdapterConfig::OpenAI(OpenAIConfig {
-            base_url: "https://api.openai.com".to_string(),
-            default_model: "text-davinci-003".to_string(),
-            token: "".to_string(),
-            temperature: 0.7,
-            top_p: Some(1.0),
-            max_tokens: 100,
-            function_calling_config: Some(FunctionCallingConfig {
-                mode: "ANY".to_string(),
-                allowed_function_names: None,
-                function_declaration_names: vec!["example_function".to_string()],
-            }),
-        }),
+        adapters,
+        roles: BTreeMap::new(),
+        context: ContextConfig::default(),
+        input: InputConfig::default(),
         logging: LoggingConfig {
             level: "info".to_string(),
         },
     };
 
-    default_config.save(home_config_path.to_str().unwrap()).expect("Failed to save default config");
-
-    println!("Created a default config file at {}. Please update it with your settings.", home_config_path.to_str().unwrap());
+    default_config.save(config_file_path.to_str().unwrap()).expect("Failed to save default config");
 
-    home_config_path
+    println!(
+        "Created a default config file at {} that reads the key from $OPENAI_API_KEY. \
+         Run `ruskgpt init` to set up a different provider.",
+        config_file_path.display()
+    );
 }
 
-pub fn handle_config_edit(matches: &ArgMatches, config: &mut Config, config_file_path: &PathBuf) -> bool {
+pub fn handle_config_edit(matches: &ArgMatches, config_file_path: &Path) -> bool {
     if matches.get_flag("edit") {
-        open_config_file_in_editor(config_file_path);
+        open_config_file_in_editor(matches, config_file_path);
         return true;
     }
 
     false
 }
 
-fn open_config_file_in_editor(config_file_path: &PathBuf) {
-    let editors = ["code", "gedit", "nano", "vi", "notepad"];
-    let editor = editors.iter()
-        .find_map(|&editor| which::which(editor).ok())
-        .unwrap_or_else(|| panic!("No suitable editor found"));
+/// Runs `ruskgpt config <subcommand>`. Returns the process exit code.
+pub fn handle_config_command(config_matches: &ArgMatches, config_file_path: &Path) -> i32 {
+    match config_matches.subcommand() {
+        Some(("check", _)) => {
+            let diagnostics = check_config(config_matches, config_file_path);
+            for diagnostic in &diagnostics {
+                println!("{}", diagnostic.display(config_file_path));
+            }
+            if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+                return 1;
+            }
+            if diagnostics.is_empty() {
+                println!("{}: OK", config_file_path.display());
+            }
+            0
+        },
+        Some(("get", args)) => {
+            let key = args.get_one::<String>("key").unwrap();
+            match config_edit::read_file(config_file_path).and_then(|doc| config_edit::get(&doc, key)) {
+                Ok(value) => {
+                    println!("{}", value);
+                    0
+                },
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    1
+                },
+            }
+        },
+        Some(("set", args)) => {
+            let key = args.get_one::<String>("key").unwrap();
+            let value = args.get_one::<String>("value").unwrap();
+            report_edit(config_matches, config_file_path, config_edit::edit_file(config_file_path, |doc| config_edit::set(doc, key, value)))
+        },
+        Some(("unset", args)) => {
+            let key = args.get_one::<String>("key").unwrap();
+            report_edit(config_matches, config_file_path, config_edit::edit_file(config_file_path, |doc| config_edit::unset(doc, key)))
+        },
+        Some(("show", args)) => {
+            match LayeredConfig::load(args, config_file_path) {
+                Ok(layered) => {
+                    println!("{}", layered.show(args.get_flag("origin")));
+                    0
+                },
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    1
+                },
+            }
+        },
+        _ => unreachable!("clap requires a config subcommand"),
+    }
+}
+
+/// Checks the user config. Required fields and values are checked with every
+/// layer applied, so the file may leave sections to the system or project
+/// config.
+fn check_config(matches: &ArgMatches, config_file_path: &Path) -> Vec<Diagnostic> {
+    match LayeredConfig::load(matches, config_file_path) {
+        Ok(layered) => config_check::check_file(config_file_path, Some(&layered.merged)),
+        Err(e) => match fs::read_to_string(config_file_path) {
+            Ok(content) => vec![config_check::syntax_error(&content)
+                .unwrap_or_else(|| Diagnostic { severity: Severity::Error, location: None, message: e.to_string() })],
+            Err(_) => config_check::check_file(config_file_path, None),
+        },
+    }
+}
+
+/// Reports a failed edit, or any errors the edit left in the file.
+fn report_edit(matches: &ArgMatches, config_file_path: &Path, result: Result<(), Box<dyn Error>>) -> i32 {
+    match result {
+        Ok(()) => {
+            let diagnostics = check_config(matches, config_file_path);
+            let errors: Vec<_> = diagnostics.iter().filter(|d| d.severity == Severity::Error).collect();
+            if !errors.is_empty() {
+                eprintln!("Saved, but the config does not load yet:");
+                for diagnostic in errors {
+                    eprintln!("  {}", diagnostic.display(config_file_path));
+                }
+            }
+            0
+        },
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        },
+    }
+}
+
+/// Loads the effective config: the user config file, upgraded on disk first
+/// if it has an older layout, merged with the other layers. On failure the
+/// problems are printed as `config check` would and the process exits.
+pub fn load_config(matches: &ArgMatches, config_file_path: &Path) -> Config {
+    if let Err(e) = config_migration::migrate_file_with_notice(config_file_path) {
+        warn!("Could not migrate {}: {}", config_file_path.display(), e);
+    }
+
+    let layered = match LayeredConfig::load(matches, config_file_path) {
+        Ok(layered) => layered,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    match layered.config() {
+        Ok(config) => config,
+        Err(e) => {
+            let diagnostics = config_check::check_file(config_file_path, Some(&layered.merged));
+            let errors: Vec<_> = diagnostics.iter().filter(|d| d.severity == Severity::Error).collect();
+            if errors.is_empty() {
+                eprintln!("Error: failed to load {}: {}", config_file_path.display(), e);
+            }
+            for diagnostic in errors {
+                eprintln!("{}", diagnostic.display(config_file_path));
+            }
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Editors tried in order when neither `$VISUAL` nor `$EDITOR` is set.
+const FALLBACK_EDITORS: &[&str] = &["code --wait", "gedit", "nano", "vi", "notepad"];
+
+/// GUI editors that return immediately unless told to wait for the file to
+/// be closed.
+const DETACHING_EDITORS: &[&str] = &["code", "code-insiders", "codium", "subl", "atom"];
+
+/// Opens the config in the user's editor and re-validates it afterwards,
+/// offering to re-open it until it loads or the user gives up.
+fn open_config_file_in_editor(matches: &ArgMatches, config_file_path: &Path) {
+    let editor = match find_editor(std::env::var("VISUAL").ok(), std::env::var("EDITOR").ok(), |program| which::which(program).is_ok()) {
+        Some(editor) => editor,
+        None => {
+            eprintln!("Error: no editor found; set $VISUAL or $EDITOR, e.g. `export EDITOR=\"code --wait\"`");
+            std::process::exit(1);
+        }
+    };
+
+    loop {
+        let status = ProcessCommand::new(&editor[0])
+            .args(&editor[1..])
+            .arg(config_file_path)
+            .status();
+        match status {
+            Ok(status) if !status.success() => warn!("Editor exited with {}", status),
+            Ok(_) => {},
+            Err(e) => {
+                eprintln!("Error: failed to run editor `{}`: {}", editor.join(" "), e);
+                std::process::exit(1);
+            }
+        }
+
+        let errors: Vec<_> = check_config(matches, config_file_path).into_iter()
+            .filter(|d| d.severity == Severity::Error)
+            .collect();
+        if errors.is_empty() {
+            return;
+        }
+        for diagnostic in &errors {
+            eprintln!("{}", diagnostic.display(config_file_path));
+        }
+        if !confirm("The config has errors. Re-open it in the editor? [Y/n] ") {
+            eprintln!("Left {} with errors; run `ruskgpt config check` after fixing it.", config_file_path.display());
+            return;
+        }
+    }
+}
+
+/// The editor command to run, split into program and arguments: `$VISUAL`,
+/// then `$EDITOR`, then the first fallback that is installed.
+fn find_editor(visual: Option<String>, editor: Option<String>, installed: impl Fn(&str) -> bool) -> Option<Vec<String>> {
+    let configured = [visual, editor].into_iter()
+        .flatten()
+        .map(|command| split_command(&command))
+        .find(|parts| !parts.is_empty());
+    let mut command = configured.or_else(|| {
+        FALLBACK_EDITORS.iter()
+            .map(|command| split_command(command))
+            .find(|parts| installed(&parts[0]))
+    })?;
+
+    let program = Path::new(&command[0]).file_stem().and_then(|stem| stem.to_str()).unwrap_or_default();
+    if DETACHING_EDITORS.contains(&program) && !command.iter().any(|arg| arg == "--wait" || arg == "-w") {
+        command.push("--wait".to_string());
+    }
+    Some(command)
+}
+
+/// Splits a command line on whitespace, keeping single- or double-quoted
+/// parts together.
+fn split_command(command: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut in_part = false;
+    let mut quote = None;
+    for c in command.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                in_part = true;
+            },
+            (None, c) if c.is_whitespace() => {
+                if in_part {
+                    parts.push(std::mem::take(&mut current));
+                    in_part = false;
+                }
+            },
+            (None, c) => {
+                current.push(c);
+                in_part = true;
+            },
+        }
+    }
+    if in_part {
+        parts.push(current);
+    }
+    parts
+}
+
+/// Asks a yes/no question on the terminal, defaulting to yes. Without a
+/// terminal the answer is no.
+fn confirm(question: &str) -> bool {
+    if !std::io::stdin().is_terminal() {
+        return false;
+    }
+    eprint!("{}", question);
+    let _ = std::io::stderr().flush();
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "" | "y" | "yes")
+}
+
+/// The selected adapter's name: `--adapter`, then the role's, then
+/// `default.adapter`.
+pub fn get_adapter_name<'a>(matches: &'a ArgMatches, config: &'a Config, role: Option<&'a RoleConfig>) -> &'a str {
+    matches.get_one::<String>("adapter")
+        .or(role.and_then(|role| role.adapter.as_ref()))
+        .unwrap_or(&config.default.adapter)
+}
 
-    ProcessCommand::new(editor)
-        .arg(config_file_path.to_str().unwrap())
-        .status()
-        .expect("Failed to open editor");
+pub fn get_adapter_config<'a>(matches: &ArgMatches, config: &'a Config, role: Option<&RoleConfig>) -> Result<&'a AdapterConfig, Box<dyn Error>> {
+    let name = get_adapter_name(matches, config, role);
+    config.adapters.get(name).ok_or_else(|| {
+        let valid_names = config.adapters.keys().cloned().collect::<Vec<_>>();
+        if valid_names.is_empty() {
+            format!("Unknown adapter '{}': no [adapters.<name>] tables are configured", name).into()
+        } else {
+            format!("Unknown adapter '{}'. Valid adapters: {}", name, valid_names.join(", ")).into()
+        }
+    })
 }
 
-pub fn get_adapter_config(config: &Config) -> &AdapterConfig {
-    &config.adapter
+/// Layers the default system prompt, the role and the command line
+/// overrides over `adapter`, then resolves its token so it is ready for the
+/// API client.
+pub fn prepare_adapter_config(
+    adapter: &AdapterConfig,
+    config: &Config,
+    role: Option<(&str, &RoleConfig)>,
+    overrides: &GenerationOverrides,
+) -> Result<AdapterConfig, Box<dyn Error>> {
+    let mut adapter_config = adapter.clone();
+
+    // A configured system prompt applies to adapters without their own
+    if adapter_config.system_prompt().is_none() {
+        if let Some(system) = &config.default.system_prompt {
+            let defaults = GenerationOverrides { system: Some(system.clone()), ..Default::default() };
+            defaults.apply(&mut adapter_config)?;
+        }
+    }
+
+    // Then the role's settings
+    if let Some((name, role)) = role {
+        role.overrides().apply(&mut adapter_config)
+            .map_err(|e| format!("role '{}': {}", name, e))?;
+    }
+
+    // Command line generation options come last
+    overrides.apply(&mut adapter_config)?;
+
+    // Resolve the token reference into the actual secret
+    adapter_config.resolve_token()?;
+    Ok(adapter_config)
 }
 
+#[allow(dead_code)] // not wired up until the agent workflow lands
 pub fn get_adapter_function_calling_config(adapter_config: &AdapterConfig) -> Option<&FunctionCallingConfig> {
     match adapter_config {
         AdapterConfig::OpenAI(config) => config.function_calling_config.as_ref(),
         AdapterConfig::Claude(config) => config.function_calling_config.as_ref(),
         AdapterConfig::Zhipu(config) => config.function_calling_config.as_ref(),
+        AdapterConfig::Gemini(config) => config.function_calling_config.as_ref(),
+        AdapterConfig::Ollama(config) => config.function_calling_config.as_ref(),
+        AdapterConfig::AzureOpenAI(config) => config.function_calling_config.as_ref(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli;
+
+    #[test]
+    fn test_find_editor() {
+        let installed = |program: &str| program == "vi";
+        let editor = |visual: Option<&str>, editor: Option<&str>| {
+            find_editor(visual.map(str::to_string), editor.map(str::to_string), installed)
+        };
+
+        assert_eq!(editor(Some("nvim"), Some("nano")).unwrap(), ["nvim"]);
+        assert_eq!(editor(None, Some("code --wait --new-window")).unwrap(), ["code", "--wait", "--new-window"]);
+        assert_eq!(editor(None, Some("/usr/bin/code")).unwrap(), ["/usr/bin/code", "--wait"]);
+        assert_eq!(editor(Some("'/opt/My Editor/edit' -f"), None).unwrap(), ["/opt/My Editor/edit", "-f"]);
+        assert_eq!(editor(Some("  "), None).unwrap(), ["vi"]);
+        assert_eq!(find_editor(None, None, |_| false), None);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_get_adapter_config() {
+        let config = Config::from_file("config.toml.example").unwrap();
+
+        let matches = cli::build_cli().get_matches_from(["ruskgpt", "hi"]);
+        assert!(matches!(get_adapter_config(&matches, &config, None).unwrap(), AdapterConfig::OpenAI(_)));
+
+        let matches = cli::build_cli().get_matches_from(["ruskgpt", "-a", "claude_adapter", "hi"]);
+        assert!(matches!(get_adapter_config(&matches, &config, None).unwrap(), AdapterConfig::Claude(_)));
+
+        // A role's preferred adapter applies unless --adapter is given
+        let role = RoleConfig { adapter: Some("claude_adapter".to_string()), ..Default::default() };
+        let matches = cli::build_cli().get_matches_from(["ruskgpt", "hi"]);
+        assert!(matches!(get_adapter_config(&matches, &config, Some(&role)).unwrap(), AdapterConfig::Claude(_)));
+        let matches = cli::build_cli().get_matches_from(["ruskgpt", "-a", "openai_adapter", "hi"]);
+        assert!(matches!(get_adapter_config(&matches, &config, Some(&role)).unwrap(), AdapterConfig::OpenAI(_)));
+
+        let matches = cli::build_cli().get_matches_from(["ruskgpt", "--adapter", "work", "hi"]);
+        assert_eq!(
+            get_adapter_config(&matches, &config, None).unwrap_err().to_string(),
+            "Unknown adapter 'work'. Valid adapters: claude_adapter, openai_adapter"
+        );
+    }
+}
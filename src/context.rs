@@ -0,0 +1,336 @@
+//! Keeps a conversation inside the model's context window. The prompt's size
+//! is estimated before it is sent, and when it would leave no room for
+//! `max_tokens`, the configured strategy shortens the conversation.
+
+use std::error::Error;
+use crate::api;
+use crate::config::{AdapterConfig, CompactionStrategy, ContextConfig};
+use crate::providers::{ChatMessage, Role};
+
+/// Used for models missing from the table and the config.
+const DEFAULT_WINDOW: u32 = 8_192;
+const DEFAULT_KEEP_LAST_TURNS: usize = 4;
+/// Framing tokens counted for every message besides its content.
+const TOKENS_PER_MESSAGE: u32 = 4;
+
+/// What `fit` did to make the conversation fit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Compaction {
+    /// Told to the user.
+    pub note: String,
+    /// The summary made by the `summarize` strategy, and how many of the
+    /// messages after `keep` it replaced. Worth saving, so the same turns
+    /// aren't summarized again.
+    pub summary: Option<(ChatMessage, usize)>,
+}
+
+/// Context windows by model name prefix; the longest matching prefix wins.
+const CONTEXT_WINDOWS: &[(&str, u32)] = &[
+    ("gpt-3.5-turbo", 16_385),
+    ("gpt-4", 8_192),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4o", 128_000),
+    ("gpt-4.1", 1_047_576),
+    ("gpt-5", 400_000),
+    ("o1", 200_000),
+    ("o3", 200_000),
+    ("o4", 200_000),
+    ("claude", 200_000),
+    ("gemini", 1_048_576),
+    ("gemini-1.5-pro", 2_097_152),
+    ("glm-4", 128_000),
+    ("deepseek", 65_536),
+    ("qwen", 32_768),
+    ("llama3", 8_192),
+    ("llama3.1", 131_072),
+    ("llama3.2", 131_072),
+    ("llama3.3", 131_072),
+    ("mistral", 32_768),
+];
+
+/// The model's context window in tokens. Entries in `[context.windows]`
+/// take precedence over the built-in table.
+pub fn context_window(model: &str, config: &ContextConfig) -> u32 {
+    if let Some(&window) = config.windows.get(model) {
+        return window;
+    }
+    let configured = config.windows.iter()
+        .filter_map(|(name, &window)| name.strip_suffix('*').map(|prefix| (prefix, window)))
+        .filter(|(prefix, _)| model.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len());
+    if let Some((_, window)) = configured {
+        return window;
+    }
+    CONTEXT_WINDOWS.iter()
+        .filter(|(prefix, _)| model.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map_or(DEFAULT_WINDOW, |&(_, window)| window)
+}
+
+/// A rough token count for some text: about four characters per token for
+/// ASCII, one per character otherwise.
+pub fn estimate_text_tokens(text: &str) -> u32 {
+    let ascii = text.bytes().filter(u8::is_ascii).count();
+    let other = text.chars().filter(|c| !c.is_ascii()).count();
+    (ascii.div_ceil(4) + other) as u32
+}
+
+pub fn estimate_tokens(messages: &[ChatMessage]) -> u32 {
+    messages.iter().map(|message| estimate_text_tokens(&message.content) + TOKENS_PER_MESSAGE).sum()
+}
+
+/// Where each turn in `messages[keep..]` starts. A turn is a question and
+/// the answer to it; the last message, the new question, is not counted.
+fn turn_starts(messages: &[ChatMessage], keep: usize) -> Vec<usize> {
+    let end = messages.len().saturating_sub(1);
+    let mut starts: Vec<usize> = (keep..end).filter(|&i| i == keep || messages[i].role == Role::User).collect();
+    starts.dedup();
+    starts
+}
+
+/// Removes all but the last `turns` turns. Returns how many were removed.
+fn keep_last(messages: &mut Vec<ChatMessage>, keep: usize, turns: usize) -> usize {
+    let starts = turn_starts(messages, keep);
+    if starts.len() <= turns {
+        return 0;
+    }
+    let cut = starts[starts.len() - turns];
+    messages.drain(keep..cut);
+    starts.len() - turns
+}
+
+/// Removes turns from the front until the conversation fits in `budget`.
+/// Returns how many were removed.
+fn drop_oldest(messages: &mut Vec<ChatMessage>, keep: usize, budget: u32) -> usize {
+    let mut dropped = 0;
+    while estimate_tokens(messages) > budget {
+        let starts = turn_starts(messages, keep);
+        let Some(&first) = starts.first() else { break };
+        let end = starts.get(1).copied().unwrap_or(messages.len() - 1);
+        messages.drain(first..end);
+        dropped += 1;
+    }
+    dropped
+}
+
+/// Has the model summarize everything but the last `turns` turns, and puts
+/// the summary in their place. Returns how many turns were summarized, and
+/// how many messages the summary replaced.
+async fn summarize(
+    messages: &mut Vec<ChatMessage>,
+    keep: usize,
+    turns: usize,
+    budget: u32,
+    adapter: &AdapterConfig,
+) -> Result<(usize, usize), Box<dyn Error>> {
+    let starts = turn_starts(messages, keep);
+    if starts.len() <= turns {
+        return Ok((0, 0));
+    }
+    let cut = starts[starts.len() - turns];
+    let mut transcript = String::new();
+    for message in &messages[keep..cut] {
+        let speaker = match message.role {
+            Role::System => "Earlier summary",
+            Role::User => "User",
+            Role::Assistant => "Assistant",
+        };
+        transcript.push_str(&format!("{}: {}\n\n", speaker, message.content.trim_end()));
+    }
+    // The older turns may not fit in one request either; keep their end
+    let limit = budget.saturating_sub(256) as usize * 4;
+    if transcript.len() > limit {
+        let start = (transcript.len() - limit..).find(|&i| transcript.is_char_boundary(i)).unwrap();
+        transcript.drain(..start);
+    }
+
+    let request = [
+        ChatMessage::system("You summarize conversations so they can be continued later. Keep names, numbers, code identifiers and decisions; leave out pleasantries."),
+        ChatMessage::user(format!("Summarize this conversation in a few short paragraphs:\n\n{}", transcript)),
+    ];
+    let summary = api::complete(adapter, &request).await.map_err(|e| format!("Failed to summarize the conversation: {}", e))?;
+    messages.splice(keep..cut, [ChatMessage::system(format!("Summary of the earlier conversation:\n{}", summary.trim()))]);
+    Ok((starts.len() - turns, cut - keep))
+}
+
+/// Shortens `messages` until the prompt leaves room for `max_tokens` in the
+/// model's context window. The first `keep` messages (system prompt and
+/// examples) and the last one (the question) are never removed. Returns
+/// what was done, if anything was removed.
+pub async fn fit(
+    messages: &mut Vec<ChatMessage>,
+    keep: usize,
+    adapter: &AdapterConfig,
+    config: &ContextConfig,
+) -> Result<Option<Compaction>, Box<dyn Error>> {
+    let window = context_window(adapter.model(), config);
+    let budget = window.saturating_sub(adapter.max_tokens().unwrap_or(0));
+    let estimate = estimate_tokens(messages);
+    if estimate <= budget {
+        return Ok(None);
+    }
+
+    let turns = config.keep_last_turns.unwrap_or(DEFAULT_KEEP_LAST_TURNS);
+    let mut summary = None;
+    let note = match config.strategy.unwrap_or_default() {
+        CompactionStrategy::DropOldest => {
+            format!("dropped the {} oldest turn(s)", drop_oldest(messages, keep, budget))
+        },
+        CompactionStrategy::KeepLast => {
+            let removed = keep_last(messages, keep, turns) + drop_oldest(messages, keep, budget);
+            format!("kept the last turns, dropping {}", removed)
+        },
+        CompactionStrategy::Summarize => {
+            let (mut summarized, replaced) = summarize(messages, keep, turns, budget, adapter).await?;
+            let made = (summarized > 0).then(|| messages[keep].clone());
+            let mut dropped = drop_oldest(messages, keep, budget);
+            // The summary is the oldest turn now, so it goes first if the
+            // prompt still doesn't fit; only a summary that was sent is kept
+            match made {
+                Some(made) if messages[keep] == made => summary = Some((made, replaced)),
+                Some(_) => {
+                    dropped += summarized - 1;
+                    summarized = 0;
+                },
+                None => {},
+            }
+            match (summarized, dropped) {
+                (0, dropped) => format!("dropped the {} oldest turn(s)", dropped),
+                (summarized, 0) => format!("summarized {} turn(s)", summarized),
+                (summarized, dropped) => format!("summarized {} turn(s) and dropped {} more", summarized, dropped),
+            }
+        },
+    };
+
+    let remaining = estimate_tokens(messages);
+    if remaining > budget {
+        return Err(format!(
+            "The prompt is about {} tokens, more than the {} that {} leaves for it ({} context, {} reserved for the answer)",
+            remaining, budget, adapter.model(), window, window - budget
+        ).into());
+    }
+    let note = format!(
+        "The conversation (about {} tokens) exceeds the {} tokens available to {}; {}.",
+        estimate, budget, adapter.model(), note
+    );
+    Ok(Some(Compaction { note, summary }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use std::collections::BTreeMap;
+
+    fn conversation(turns: usize) -> Vec<ChatMessage> {
+        let mut messages = vec![ChatMessage::system("Be brief.")];
+        for i in 0..turns {
+            messages.push(ChatMessage::user(format!("question {} {}", i, "x".repeat(400))));
+            messages.push(ChatMessage::assistant(format!("answer {} {}", i, "y".repeat(400))));
+        }
+        messages.push(ChatMessage::user("last question"));
+        messages
+    }
+
+    fn openai_adapter(model: &str, max_tokens: u32) -> AdapterConfig {
+        let config = Config::from_file("config.toml.example").unwrap();
+        let mut adapter = config.adapters["openai_adapter"].clone();
+        let AdapterConfig::OpenAI(openai) = &mut adapter else { unreachable!() };
+        openai.default_model = model.to_string();
+        openai.max_tokens = max_tokens;
+        openai.token = "sk-test".to_string();
+        adapter
+    }
+
+    #[test]
+    fn test_context_window() {
+        let mut config = ContextConfig::default();
+        assert_eq!(context_window("gpt-4o-mini", &config), 128_000);
+        assert_eq!(context_window("gpt-4-0613", &config), 8_192);
+        assert_eq!(context_window("claude-sonnet-4", &config), 200_000);
+        assert_eq!(context_window("my-finetune", &config), DEFAULT_WINDOW);
+
+        config.windows = BTreeMap::from([("gpt-4o*".to_string(), 64_000), ("gpt-4o-mini".to_string(), 32_000)]);
+        assert_eq!(context_window("gpt-4o-2024-08-06", &config), 64_000);
+        assert_eq!(context_window("gpt-4o-mini", &config), 32_000);
+    }
+
+    #[test]
+    fn test_estimate_tokens() {
+        assert_eq!(estimate_text_tokens("abcdefgh"), 2);
+        assert_eq!(estimate_text_tokens("你好"), 2);
+        assert_eq!(estimate_tokens(&[ChatMessage::user("abcd")]), 1 + TOKENS_PER_MESSAGE);
+    }
+
+    #[tokio::test]
+    async fn test_drop_oldest_and_keep_last() {
+        // Each turn is about 210 tokens; leave room for about three
+        let adapter = openai_adapter("gpt-4", 8_192 - 700);
+        let mut messages = conversation(6);
+        let compaction = fit(&mut messages, 1, &adapter, &ContextConfig::default()).await.unwrap().unwrap();
+        assert_eq!(compaction.note.split("; ").last(), Some("dropped the 3 oldest turn(s)."));
+        assert_eq!(compaction.summary, None);
+        assert_eq!(messages.len(), 1 + 3 * 2 + 1);
+        assert!(messages[1].content.starts_with("question 3"));
+        assert_eq!(messages.last(), Some(&ChatMessage::user("last question")));
+
+        let config = ContextConfig { strategy: Some(CompactionStrategy::KeepLast), keep_last_turns: Some(2), ..Default::default() };
+        let mut messages = conversation(6);
+        fit(&mut messages, 1, &adapter, &config).await.unwrap();
+        assert!(messages[1].content.starts_with("question 4"));
+
+        // Nothing is removed when it already fits
+        let mut messages = conversation(2);
+        assert_eq!(fit(&mut messages, 1, &adapter, &config).await.unwrap(), None);
+        assert_eq!(messages, conversation(2));
+    }
+
+    #[tokio::test]
+    async fn test_question_too_long() {
+        let adapter = openai_adapter("gpt-4", 8_000);
+        let mut messages = vec![ChatMessage::system("Be brief."), ChatMessage::user("z".repeat(4_000))];
+        let error = fit(&mut messages, 1, &adapter, &ContextConfig::default()).await.unwrap_err();
+        assert!(error.to_string().starts_with("The prompt is about 1011 tokens, more than the 192"));
+    }
+
+    #[tokio::test]
+    async fn test_summarize() {
+        let body = "data: {\"choices\":[{\"delta\":{\"content\":\"They asked five questions.\"}}]}\n\ndata: [DONE]\n\n";
+        let (base_url, request) = crate::providers::mock_server::serve_once(200, "text/event-stream", body).await;
+        let mut adapter = openai_adapter("gpt-4", 8_192 - 1_500);
+        let AdapterConfig::OpenAI(openai) = &mut adapter else { unreachable!() };
+        openai.base_url = base_url;
+
+        let config = ContextConfig { strategy: Some(CompactionStrategy::Summarize), keep_last_turns: Some(3), ..Default::default() };
+        let mut messages = conversation(8);
+        let compaction = fit(&mut messages, 1, &adapter, &config).await.unwrap().unwrap();
+        assert!(compaction.note.ends_with("summarized 5 turn(s)."));
+
+        let request = request.await.unwrap();
+        assert!(request.contains("User: question 0") && request.contains("Assistant: answer 4"));
+        assert!(!request.contains("question 5"));
+        assert_eq!(messages.len(), 1 + 1 + 3 * 2 + 1);
+        assert_eq!(messages[1], ChatMessage::system("Summary of the earlier conversation:\nThey asked five questions."));
+        assert!(messages[2].content.starts_with("question 5"));
+        assert_eq!(compaction.summary, Some((messages[1].clone(), 5 * 2)));
+    }
+
+    #[tokio::test]
+    async fn test_summary_that_does_not_fit() {
+        let body = "data: {\"choices\":[{\"delta\":{\"content\":\"They asked one question.\"}}]}\n\ndata: [DONE]\n\n";
+        let (base_url, _request) = crate::providers::mock_server::serve_once(200, "text/event-stream", body).await;
+        let mut adapter = openai_adapter("gpt-4", 8_192 - 1_500);
+        let AdapterConfig::OpenAI(openai) = &mut adapter else { unreachable!() };
+        openai.base_url = base_url;
+
+        // The seven kept turns are too long on their own, so the summary is
+        // dropped along with the oldest of them, and not returned
+        let config = ContextConfig { strategy: Some(CompactionStrategy::Summarize), keep_last_turns: Some(7), ..Default::default() };
+        let mut messages = conversation(8);
+        let compaction = fit(&mut messages, 1, &adapter, &config).await.unwrap().unwrap();
+        assert!(compaction.note.ends_with("dropped the 2 oldest turn(s)."), "{}", compaction.note);
+        assert_eq!(compaction.summary, None);
+        assert!(messages.iter().all(|message| message.role != Role::System || message.content == "Be brief."));
+        assert!(messages[1].content.starts_with("question 2"));
+    }
+}
@@ -1,9 +1,9 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::fs;
-use std::path::Path;
-use regex::Regex;
+use std::path::PathBuf;
+use crate::paths::Paths;
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct FunctionParameter {
@@ -72,8 +72,28 @@ pub enum FunctionDeclaration {
     },
 }
 
+impl FunctionDeclaration {
+    pub fn name(&self) -> &str {
+        match self {
+            FunctionDeclaration::Shell { name, .. } => name,
+            FunctionDeclaration::Interactive { name, .. } => name,
+        }
+    }
+}
+
+/// Directories searched for ability files: `$XDG_DATA_HOME/ruskgpt/abilities`,
+/// then the `abilities/` directory shipped with the source.
+fn abilities_dirs() -> Vec<PathBuf> {
+    vec![Paths::new().abilities_dir(), PathBuf::from("abilities")]
+}
+
+#[allow(dead_code)] // not wired up until the agent workflow lands
 pub fn load_function_declaration(name: &str) -> Result<FunctionDeclaration, Box<dyn Error>> {
-    let path = Path::new("abilities").join(format!("{}.toml", name));
+    let file_name = format!("{}.toml", name);
+    let path = abilities_dirs().into_iter()
+        .map(|dir| dir.join(&file_name))
+        .find(|path| path.is_file())
+        .ok_or_else(|| format!("No ability named '{}'", name))?;
     let content = fs::read_to_string(path)?;
     let function_declaration: FunctionDeclaration = toml::from_str(&content)?;
     Ok(function_declaration)
@@ -81,18 +101,26 @@ pub fn load_function_declaration(name: &str) -> Result<FunctionDeclaration, Box<
 
 pub fn list_function_declarations() -> Result<Vec<FunctionDeclaration>, Box<dyn Error>> {
     let mut functions = Vec::new();
-    for entry in fs::read_dir("abilities")? {
-        let entry = entry?;
-        let path = entry.path();
-        if path.extension().and_then(|s| s.to_str()) == Some("toml") {
-            let content = fs::read_to_string(&path)?;
-            let function_declaration: FunctionDeclaration = toml::from_str(&content)?;
-            functions.push(function_declaration);
+    let mut seen = HashSet::new();
+    for dir in abilities_dirs().into_iter().filter(|dir| dir.is_dir()) {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if path.extension().and_then(|s| s.to_str()) == Some("toml") {
+                // An ability in the data dir shadows the bundled one of the same file name
+                if !seen.insert(entry.file_name()) {
+                    continue;
+                }
+                let content = fs::read_to_string(&path)?;
+                let function_declaration: FunctionDeclaration = toml::from_str(&content)?;
+                functions.push(function_declaration);
+            }
         }
     }
     Ok(functions)
 }
 
+#[allow(dead_code)] // not wired up until the agent workflow lands
 pub fn generate_command(function: FunctionDeclaration, llm_params: &[(&str, &str)]) -> String {
     let mut command = match &function {
         FunctionDeclaration::Shell { command_template, .. } => command_template.clone(),
@@ -0,0 +1,434 @@
+//! `ruskgpt init`: sets up an adapter, sends a tiny test request and saves
+//! it to the user config. Runs as a questionnaire, or from flags with
+//! `--non-interactive` for scripts.
+
+use clap::ArgMatches;
+use futures::StreamExt;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use toml_edit::{value, DocumentMut, Item};
+use crate::api_client::ApiClient;
+use crate::config::{
+    self, AdapterConfig, AzureOpenAIConfig, ChatGLMConfig, ClaudeConfig, Config, ContextConfig, DefaultConfig, GeminiConfig,
+    InputConfig, LoggingConfig, OllamaConfig, OpenAIConfig,
+};
+use crate::config_edit;
+use crate::config_migration::CURRENT_CONFIG_VERSION;
+use crate::overrides::GenerationOverrides;
+use crate::providers::{ChatMessage, StreamEvent};
+
+/// Provider type, default base URL, default model and the environment
+/// variable its key usually lives in. Azure has no default base URL; each
+/// resource has its own.
+const PROVIDERS: &[(&str, &str, &str, &str)] = &[
+    ("OpenAI", "https://api.openai.com/v1", "gpt-4o-mini", "OPENAI_API_KEY"),
+    ("Claude", "https://api.anthropic.com/v1", "claude-3-5-haiku-latest", "ANTHROPIC_API_KEY"),
+    ("Zhipu", "https://open.bigmodel.cn/api/paas/v4", "glm-4-flash", "ZHIPUAI_API_KEY"),
+    ("Gemini", "https://generativelanguage.googleapis.com/v1beta", "gemini-1.5-flash", "GEMINI_API_KEY"),
+    ("Ollama", "http://localhost:11434", "llama3.2", ""),
+    ("AzureOpenAI", "", "", "AZURE_OPENAI_API_KEY"),
+];
+
+const AZURE_BASE_URL_EXAMPLE: &str = "https://<resource>.openai.azure.com";
+const TEST_PROMPT: &str = "Reply with the single word OK.";
+const TEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Where the adapter's key comes from, as written to the config.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TokenSource {
+    pub token: String,
+    pub token_cmd: Option<String>,
+    pub token_file: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct InitOptions {
+    pub provider: String,
+    pub name: String,
+    pub base_url: String,
+    pub model: String,
+    pub deployment: Option<String>,
+    pub api_version: Option<String>,
+    pub token: TokenSource,
+}
+
+/// Runs `ruskgpt init`. Returns the process exit code.
+pub async fn run(matches: &ArgMatches, config_file_path: &Path) -> i32 {
+    let interactive = !matches.get_flag("non-interactive");
+    let options = if interactive {
+        ask_options(&mut std::io::stdin().lock())
+    } else {
+        options_from_matches(matches)
+    };
+    let options = match options {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return 1;
+        }
+    };
+    let adapter = build_adapter(&options);
+
+    if adapter_exists(config_file_path, &options.name) {
+        let replace = if interactive {
+            ask_yes_no(
+                &mut std::io::stdin().lock(),
+                &format!("Adapter '{}' already exists in {}. Replace it?", options.name, config_file_path.display()),
+                false,
+            )
+        } else {
+            matches.get_flag("force")
+        };
+        if !replace {
+            if interactive {
+                eprintln!("Nothing was saved.");
+            } else {
+                eprintln!(
+                    "Error: adapter '{}' already exists in {}; pass --force to replace it, or --name to save it under another name",
+                    options.name, config_file_path.display()
+                );
+            }
+            return 1;
+        }
+    }
+
+    if !matches.get_flag("skip-test") {
+        println!("Sending a test request to {}...", options.base_url);
+        match test_connection(&adapter).await {
+            Ok((latency, reply)) => {
+                println!("OK in {} ms, {} replied: {}", latency.as_millis(), options.model_or_deployment(), reply.trim());
+            },
+            Err(e) => {
+                eprintln!("Test request failed: {}", e);
+                if !interactive || !ask_yes_no(&mut std::io::stdin().lock(), "Save the adapter anyway?", false) {
+                    eprintln!("Nothing was saved.");
+                    return 1;
+                }
+            },
+        }
+    }
+
+    match save_adapter(config_file_path, &options.name, &adapter) {
+        Ok(()) => {
+            println!("Saved adapter '{}' to {} and made it the default.", options.name, config_file_path.display());
+            0
+        },
+        Err(e) => {
+            eprintln!("Error: failed to save {}: {}", config_file_path.display(), e);
+            1
+        }
+    }
+}
+
+impl InitOptions {
+    fn model_or_deployment(&self) -> &str {
+        self.deployment.as_deref().unwrap_or(&self.model)
+    }
+}
+
+fn provider_defaults(provider: &str) -> Result<(&'static str, &'static str, &'static str, &'static str), Box<dyn Error>> {
+    PROVIDERS.iter()
+        .find(|(name, ..)| name.eq_ignore_ascii_case(provider))
+        .copied()
+        .ok_or_else(|| {
+            let names = PROVIDERS.iter().map(|(name, ..)| *name).collect::<Vec<_>>();
+            format!("Unknown provider '{}'. Valid providers: {}", provider, names.join(", ")).into()
+        })
+}
+
+fn options_from_matches(matches: &ArgMatches) -> Result<InitOptions, Box<dyn Error>> {
+    let provider = matches.get_one::<String>("provider")
+        .ok_or("--provider is required with --non-interactive")?;
+    let (provider, base_url, model, key_env) = provider_defaults(provider)?;
+    let arg = |id: &str| matches.get_one::<String>(id).cloned();
+
+    let token = match (arg("token"), arg("token-env"), arg("token-cmd"), arg("token-file")) {
+        (Some(token), None, None, None) => TokenSource { token, ..Default::default() },
+        (None, Some(var), None, None) => TokenSource { token: format!("${{{}}}", var), ..Default::default() },
+        (None, None, Some(command), None) => TokenSource { token_cmd: Some(command), ..Default::default() },
+        (None, None, None, Some(file)) => TokenSource { token_file: Some(file), ..Default::default() },
+        (None, None, None, None) if key_env.is_empty() => TokenSource::default(),
+        (None, None, None, None) => TokenSource { token: format!("${{{}}}", key_env), ..Default::default() },
+        _ => return Err("Use only one of --token, --token-env, --token-cmd and --token-file".into()),
+    };
+
+    let options = InitOptions {
+        provider: provider.to_string(),
+        name: arg("name").unwrap_or_else(|| provider.to_lowercase()),
+        base_url: arg("base-url").unwrap_or_else(|| base_url.to_string()),
+        model: arg("model").unwrap_or_else(|| model.to_string()),
+        deployment: arg("deployment"),
+        api_version: arg("api-version"),
+        token,
+    };
+    if options.base_url.is_empty() {
+        return Err(format!("The {} provider needs --base-url, e.g. {}", provider, AZURE_BASE_URL_EXAMPLE).into());
+    }
+    if provider == "AzureOpenAI" && options.deployment.is_none() {
+        return Err("The AzureOpenAI provider needs --deployment".into());
+    }
+    Ok(options)
+}
+
+fn ask_options(input: &mut impl BufRead) -> Result<InitOptions, Box<dyn Error>> {
+    println!("Providers:");
+    for (i, (name, ..)) in PROVIDERS.iter().enumerate() {
+        println!("  {}. {}", i + 1, name);
+    }
+    let choice = ask(input, "Provider", "1")?;
+    let provider = match choice.parse::<usize>() {
+        Ok(n) if (1..=PROVIDERS.len()).contains(&n) => PROVIDERS[n - 1].0,
+        _ => provider_defaults(&choice)?.0,
+    };
+    let (provider, default_base_url, default_model, key_env) = provider_defaults(provider)?;
+
+    let name = ask(input, "Adapter name", &provider.to_lowercase())?;
+    let base_url = if default_base_url.is_empty() {
+        ask(input, &format!("Base URL, e.g. {}", AZURE_BASE_URL_EXAMPLE), "")?
+    } else {
+        ask(input, "Base URL", default_base_url)?
+    };
+    let (model, deployment, api_version) = if provider == "AzureOpenAI" {
+        (String::new(), Some(ask(input, "Deployment", "")?), Some(ask(input, "API version", "2024-06-01")?))
+    } else {
+        (ask(input, "Model", default_model)?, None, None)
+    };
+
+    let token = if key_env.is_empty() {
+        TokenSource::default()
+    } else {
+        println!("Where does the API key come from?");
+        println!("  1. An environment variable");
+        println!("  2. A command that prints it, e.g. `pass show openai`");
+        println!("  3. A file");
+        println!("  4. Paste it (stored in plaintext)");
+        match ask(input, "Key source", "1")?.as_str() {
+            "2" => TokenSource { token_cmd: Some(ask(input, "Command", "")?), ..Default::default() },
+            "3" => TokenSource { token_file: Some(ask(input, "File", "")?), ..Default::default() },
+            "4" => TokenSource { token: ask(input, "API key", "")?, ..Default::default() },
+            _ => TokenSource { token: format!("${{{}}}", ask(input, "Variable", key_env)?), ..Default::default() },
+        }
+    };
+
+    Ok(InitOptions { provider: provider.to_string(), name, base_url, model, deployment, api_version, token })
+}
+
+/// Prompts for a value, returning `default` for an empty answer.
+fn ask(input: &mut impl BufRead, question: &str, default: &str) -> Result<String, Box<dyn Error>> {
+    if default.is_empty() {
+        print!("{}: ", question);
+    } else {
+        print!("{} [{}]: ", question, default);
+    }
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    if input.read_line(&mut answer)? == 0 {
+        return Err("Setup cancelled".into());
+    }
+    let answer = answer.trim();
+    match (answer.is_empty(), default.is_empty()) {
+        (false, _) => Ok(answer.to_string()),
+        (true, false) => Ok(default.to_string()),
+        (true, true) => Err(format!("{} is required", question).into()),
+    }
+}
+
+fn ask_yes_no(input: &mut impl BufRead, question: &str, default: bool) -> bool {
+    let hint = if default { "Y/n" } else { "y/N" };
+    match ask(input, &format!("{} [{}]", question, hint), "") {
+        Ok(answer) => matches!(answer.to_lowercase().as_str(), "y" | "yes"),
+        Err(_) => default,
+    }
+}
+
+pub fn build_adapter(options: &InitOptions) -> AdapterConfig {
+    let TokenSource { token, token_cmd, token_file } = options.token.clone();
+    let base_url = options.base_url.clone();
+    let default_model = options.model.clone();
+    match options.provider.as_str() {
+        "Claude" => AdapterConfig::Claude(ClaudeConfig {
+            base_url, default_model, token, token_cmd, token_file, temperature: 0.7, max_tokens: 2048, ..Default::default()
+        }),
+        "Zhipu" => AdapterConfig::Zhipu(ChatGLMConfig {
+            base_url, default_model, token, token_cmd, token_file, temperature: 0.7, max_tokens: 2048, ..Default::default()
+        }),
+        "Gemini" => AdapterConfig::Gemini(GeminiConfig {
+            base_url, default_model, token, token_cmd, token_file, temperature: 0.7, max_tokens: 2048, ..Default::default()
+        }),
+        "Ollama" => AdapterConfig::Ollama(OllamaConfig {
+            base_url, default_model, token: Some(token).filter(|token| !token.is_empty()), token_cmd, token_file,
+            ..Default::default()
+        }),
+        "AzureOpenAI" => AdapterConfig::AzureOpenAI(AzureOpenAIConfig {
+            base_url,
+            deployment: options.deployment.clone().unwrap_or_default(),
+            api_version: options.api_version.clone().unwrap_or_else(|| "2024-06-01".to_string()),
+            token, token_cmd, token_file, temperature: 0.7, max_tokens: 2048, ..Default::default()
+        }),
+        _ => AdapterConfig::OpenAI(OpenAIConfig {
+            base_url, default_model, token, token_cmd, token_file, temperature: 0.7, max_tokens: 2048, ..Default::default()
+        }),
+    }
+}
+
+/// Sends a one-line prompt through the adapter and returns how long the
+/// full reply took, and the reply.
+pub async fn test_connection(adapter: &AdapterConfig) -> Result<(Duration, String), Box<dyn Error>> {
+    let mut adapter = adapter.clone();
+    GenerationOverrides { max_tokens: Some(16), ..Default::default() }.apply(&mut adapter)?;
+    adapter.resolve_token()?;
+
+    let client = ApiClient::new(adapter);
+    let start = Instant::now();
+    let reply = tokio::time::timeout(TEST_TIMEOUT, async {
+        let mut stream = client.stream_chat(&[ChatMessage::user(TEST_PROMPT)]).await?;
+        let mut reply = String::new();
+        while let Some(event) = stream.next().await {
+            if let StreamEvent::Text(text) = event? {
+                reply.push_str(&text);
+            }
+        }
+        Ok::<_, Box<dyn Error>>(reply)
+    }).await.map_err(|_| format!("no reply within {} seconds", TEST_TIMEOUT.as_secs()))??;
+
+    Ok((start.elapsed(), reply))
+}
+
+/// Whether the config at `path` already has an adapter called `name`.
+fn adapter_exists(path: &Path, name: &str) -> bool {
+    config_edit::read_file(path)
+        .is_ok_and(|doc| doc.get("adapters").and_then(|adapters| adapters.get(name)).is_some())
+}
+
+/// Adds the adapter to the config, or creates the config, and makes it the
+/// default. Comments in an existing file are kept.
+pub fn save_adapter(path: &Path, name: &str, adapter: &AdapterConfig) -> Result<(), Box<dyn Error>> {
+    if !path.exists() {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let config = Config {
+            version: CURRENT_CONFIG_VERSION,
+            default: DefaultConfig { adapter: name.to_string(), ..Default::default() },
+            adapters: BTreeMap::from([(name.to_string(), adapter.clone())]),
+            roles: BTreeMap::new(),
+            context: ContextConfig::default(),
+            input: InputConfig::default(),
+            logging: LoggingConfig { level: "info".to_string() },
+        };
+        return config.save(path.to_str().unwrap());
+    }
+
+    let table: DocumentMut = config::to_toml_string(adapter)?.parse()?;
+    config_edit::edit_file(path, |doc| {
+        if !doc.contains_key("adapters") {
+            let mut adapters = toml_edit::Table::new();
+            adapters.set_implicit(true);
+            doc.insert("adapters", Item::Table(adapters));
+        }
+        let adapters = doc["adapters"].as_table_mut().ok_or("`adapters` is not a table")?;
+        adapters.insert(name, Item::Table(table.as_table().clone()));
+        doc["default"]["adapter"] = value(name);
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::mock_server;
+
+    fn openai_options(base_url: String) -> InitOptions {
+        InitOptions {
+            provider: "OpenAI".to_string(),
+            name: "local".to_string(),
+            base_url,
+            model: "gpt-4o-mini".to_string(),
+            token: TokenSource { token: "sk-test".to_string(), ..Default::default() },
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connection_against_mock() {
+        let body = "data: {\"choices\":[{\"delta\":{\"content\":\"OK\"}}]}\n\ndata: [DONE]\n\n";
+        let (base_url, server) = mock_server::serve_once(200, "text/event-stream", body).await;
+        let (_, reply) = test_connection(&build_adapter(&openai_options(base_url))).await.unwrap();
+        assert_eq!(reply, "OK");
+        assert!(server.await.unwrap().contains("\"max_tokens\":16"));
+
+        let body = r#"{"error":{"message":"Incorrect API key provided","type":"invalid_request_error"}}"#;
+        let (base_url, _server) = mock_server::serve_once(401, "application/json", body).await;
+        let error = test_connection(&build_adapter(&openai_options(base_url))).await.unwrap_err();
+        assert!(error.to_string().contains("Incorrect API key provided"), "{}", error);
+    }
+
+    #[test]
+    fn test_interactive_answers() {
+        let mut input = "5\n\n\nqwen2.5\n".as_bytes();
+        let options = ask_options(&mut input).unwrap();
+        assert_eq!(options.provider, "Ollama");
+        assert_eq!(options.name, "ollama");
+        assert_eq!(options.base_url, "http://localhost:11434");
+        assert_eq!(options.model, "qwen2.5");
+        assert_eq!(options.token, TokenSource::default());
+
+        let mut input = "claude\nwork\n\n\n2\npass show anthropic\n".as_bytes();
+        let options = ask_options(&mut input).unwrap();
+        assert_eq!(options.provider, "Claude");
+        assert_eq!(options.token.token_cmd.as_deref(), Some("pass show anthropic"));
+    }
+
+    #[test]
+    fn test_save_adapter_keeps_existing_config() {
+        let path = std::env::temp_dir().join(format!("ruskgpt-init-{}.toml", std::process::id()));
+        fs::write(&path, fs::read_to_string("config.toml.example").unwrap().replace("[logging]", "# keep me\n[logging]")).unwrap();
+
+        let options = InitOptions { token: TokenSource { token: "${LOCAL_KEY}".to_string(), ..Default::default() }, ..openai_options("http://127.0.0.1:8080/v1".to_string()) };
+        save_adapter(&path, "local", &build_adapter(&options)).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(content.contains("# keep me"));
+        assert!(content.contains("[adapters.local]\ntype = \"OpenAI\"\nbase_url = \"http://127.0.0.1:8080/v1\""));
+        assert!(content.contains("token = \"${LOCAL_KEY}\""));
+        assert!(content.contains("temperature = 0.7\n"));
+        let config: Config = toml::from_str(&content).unwrap();
+        assert_eq!(config.default.adapter, "local");
+        assert_eq!(config.adapters.len(), 3);
+    }
+
+    #[test]
+    fn test_azure_needs_base_url() {
+        let matches = |args: &[&str]| {
+            let mut argv = vec!["ruskgpt", "init", "--non-interactive", "--provider", "AzureOpenAI", "--deployment", "gpt4o"];
+            argv.extend_from_slice(args);
+            let matches = crate::cli::build_cli().try_get_matches_from(argv).unwrap();
+            let (_, init_matches) = matches.subcommand().unwrap();
+            options_from_matches(init_matches)
+        };
+        let error = matches(&[]).unwrap_err().to_string();
+        assert_eq!(error, "The AzureOpenAI provider needs --base-url, e.g. https://<resource>.openai.azure.com");
+        let options = matches(&["--base-url", "https://contoso.openai.azure.com"]).unwrap();
+        assert_eq!(options.base_url, "https://contoso.openai.azure.com");
+
+        // Interactively, the base URL has to be typed in
+        let mut input = "6\n\n\n".as_bytes();
+        assert_eq!(ask_options(&mut input).unwrap_err().to_string(), "Base URL, e.g. https://<resource>.openai.azure.com is required");
+    }
+
+    #[test]
+    fn test_adapter_exists() {
+        let path = std::env::temp_dir().join(format!("ruskgpt-init-exists-{}.toml", std::process::id()));
+        fs::copy("config.toml.example", &path).unwrap();
+        assert!(adapter_exists(&path, "openai_adapter"));
+        assert!(!adapter_exists(&path, "local"));
+        fs::remove_file(&path).unwrap();
+        assert!(!adapter_exists(&path, "openai_adapter"));
+    }
+}
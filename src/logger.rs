@@ -1,12 +1,16 @@
 use flexi_logger::{Logger, FileSpec, Criterion, Naming, Cleanup};
+use std::fs;
+use crate::paths::Paths;
 
 pub fn initialize_logger() {
-    let temp_dir = std::env::temp_dir();
+    // Logs belong in $XDG_STATE_HOME; fall back to the temp dir if it can't be created
+    let log_dir = Paths::new().log_dir();
+    let log_dir = if fs::create_dir_all(&log_dir).is_ok() { log_dir } else { std::env::temp_dir() };
     let log_file_name = "ruskgpt";
     
     Logger::try_with_str("info")
         .unwrap()
-        .log_to_file(FileSpec::default().directory(temp_dir).basename(log_file_name))
+        .log_to_file(FileSpec::default().directory(log_dir).basename(log_file_name))
         .rotate(
             Criterion::Size(10_000_000), // Rotate log file after it reaches 10 MB
             Naming::Timestamps,          // Use timestamps for rotated file names
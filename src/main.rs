@@ -1,15 +1,32 @@
 mod config;
+mod config_check;
+mod config_edit;
+mod config_layers;
+mod config_migration;
+mod context;
 mod api_client;
 mod logger;
 mod cli;
 mod config_handler;
 mod api;
+mod attachments;
+mod chat;
 mod process_response;
 mod functional_calling;
+mod init;
+mod overrides;
+mod paths;
+mod piped_input;
+mod providers;
+mod roles;
+mod secrets;
+mod session_export;
+mod session_import;
+mod sessions;
+mod sse;
 
-use api_client::ApiClient;
-use config::Config;
-use functional_calling::{list_function_declarations, load_function_declaration, FunctionDeclaration};
+use std::io::IsTerminal;
+use functional_calling::{list_function_declarations, FunctionDeclaration};
 use tokio::main;
 
 #[main]
@@ -20,13 +37,58 @@ async fn main() {
     // Parse command line arguments
     let matches = cli::parse_command_line_arguments();
 
+    // Print resolved locations without touching any of them
+    if let Some(("paths", _)) = matches.subcommand() {
+        paths::print_paths(&paths::Paths::new(), &config_handler::peek_config_file_path(&matches));
+        return;
+    }
+
+    // Determine config file path
+    let config_file_path = config_handler::get_config_file_path(&matches);
+
+    // Handle `sessions` subcommands
+    let session_store = sessions::SessionStore::new(paths::Paths::new().sessions_dir());
+    if let Some(("sessions", sessions_matches)) = matches.subcommand() {
+        std::process::exit(sessions::handle_sessions_command(sessions_matches, &session_store));
+    }
+
+    // Run the guided setup
+    if let Some(("init", init_matches)) = matches.subcommand() {
+        std::process::exit(init::run(init_matches, &config_file_path).await);
+    }
+
+    // First run: write a starter config
+    if !config_file_path.exists() && matches.get_one::<String>("config").is_none() {
+        config_handler::create_default_config(&config_file_path);
+    }
+
+    // Handle `config` subcommands
+    if let Some(("config", config_matches)) = matches.subcommand() {
+        std::process::exit(config_handler::handle_config_command(config_matches, &config_file_path));
+    }
+
+    // Handle configuration updates or editing
+    if config_handler::handle_config_edit(&matches, &config_file_path) {
+        return;
+    }
+
+    // The options for this question; global ones may follow `chat`
+    let args = match matches.subcommand() {
+        Some(("chat", chat_matches)) => chat_matches,
+        _ => &matches,
+    };
+
+    // Load config
+    let config = config_handler::load_config(args, &config_file_path);
+
     // Check if list-functions flag is set
     let list_functions = matches.get_flag("list-functions");
 
     if list_functions {
         match list_function_declarations() {
             Ok(functions) => {
-                for function in functions {
+                let allowed = config.default.allowed_abilities.as_ref();
+                for function in functions.into_iter().filter(|f| allowed.is_none_or(|names| names.iter().any(|n| n == f.name()))) {
                     match function {
                         FunctionDeclaration::Shell { name, description, parameters, command_template } => {
                             println!("Function: {}", name);
@@ -53,36 +115,146 @@ async fn main() {
         return;
     }
 
-    // Determine config file path
-    let config_file_path = config_handler::get_config_file_path(&matches);
+    // Handle `roles` subcommands
+    if let Some(("roles", roles_matches)) = matches.subcommand() {
+        std::process::exit(roles::handle_roles_command(roles_matches, &config));
+    }
 
-    // Load config
-    let mut config = Config::from_file(config_file_path.to_str().unwrap())
-        .expect("Failed to load config");
+    // Get the role, if one was selected
+    let role_name = args.get_one::<String>("role");
+    let role = match role_name.map(|name| roles::get_role(&config, name)) {
+        Some(Ok(role)) => Some(role),
+        Some(Err(e)) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        },
+        None => None,
+    };
 
-    // Handle configuration updates or editing
-    if config_handler::handle_config_edit(&matches, &mut config, &config_file_path) {
-        return;
+    // Get adapter config
+    let adapter_name = config_handler::get_adapter_name(args, &config, role.as_ref()).to_string();
+    let base_adapter_config = match config_handler::get_adapter_config(args, &config, role.as_ref()) {
+        Ok(adapter_config) => adapter_config,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // Layer the default system prompt, the role and the command line options
+    // over the adapter config, and resolve its token
+    let overrides = overrides::GenerationOverrides::from_matches(args);
+    let role_with_name = role_name.zip(role.as_ref()).map(|(name, role)| (name.as_str(), role));
+    let adapter_config = match config_handler::prepare_adapter_config(base_adapter_config, &config, role_with_name, &overrides) {
+        Ok(adapter_config) => adapter_config,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let render_options = api::RenderOptions {
+        show_reasoning: args.get_flag("show-reasoning"),
+        terminal: std::io::stdout().is_terminal(),
+    };
+
+    // The conversation this question belongs to
+    let session = match sessions::select_session(args, &session_store) {
+        Ok(session) => session,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // The question, with anything piped into stdin
+    let chat_requested = matches!(matches.subcommand(), Some(("chat", _)));
+    let question = if chat_requested {
+        None
+    } else {
+        match piped_input::read_prompt(matches.get_one::<String>("question").map(String::as_str), &config.input) {
+            Ok(question) => question,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    };
+
+    // Files attached with --file go along with a question
+    let files: Vec<String> = matches.get_many::<String>("file").map(|files| files.cloned().collect()).unwrap_or_default();
+    if !files.is_empty() && question.is_none() {
+        eprintln!("Error: --file attaches files to a question, e.g. ruskgpt -f src/main.rs \"what does this do?\"");
+        std::process::exit(1);
     }
 
-    // Get the question
-    let prompt = matches.get_one::<String>("question")
-        .expect("Usage: ruskgpt <your_question>");
+    // Without a question, chat on a terminal
+    let Some(mut prompt) = question else {
+        if !(std::io::stdin().is_terminal() && std::io::stdout().is_terminal()) {
+            eprintln!("Usage: ruskgpt <your_question>");
+            std::process::exit(1);
+        }
+        let settings = chat::ChatSettings {
+            config: &config,
+            adapter_name,
+            adapter: adapter_config,
+            role: role_name.cloned().zip(role),
+            overrides,
+            options: render_options,
+        };
+        std::process::exit(chat::run(chat::Chat::new(settings, session_store, session)).await);
+    };
 
-    // Get adapter config
-    let adapter_config = config_handler::get_adapter_config(&config);
+    if !files.is_empty() {
+        let attachments = match attachments::attach(&files, &config.input) {
+            Ok(attachments) => attachments,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        };
+        attachments.report();
+        if attachments.files == 0 {
+            eprintln!("Error: none of the attached files could be read");
+            std::process::exit(1);
+        }
+        prompt = format!("{}\n\n{}", prompt, attachments.text.trim_end());
+    }
 
     // Check if agent functionality is enabled
     let enable_agent = matches.get_flag("agent");
 
-    // Create API client
-    let client = ApiClient::new(adapter_config.clone());
-
     if enable_agent {
         // Placeholder for future workflow logic
         panic!("Agent functionality is not yet implemented");
     } else {
-        // Process response stream
-        api::process_response_stream(adapter_config, prompt).await;
+        // Process response stream, following on from the session
+        let examples = role.map(|role| role.examples).unwrap_or_default();
+        let mut messages = api::build_messages(&adapter_config, &examples, &session.chat_messages(), &prompt);
+        let compaction = match context::fit(&mut messages, 1 + examples.len(), &adapter_config, &config.context).await {
+            Ok(compaction) => compaction,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        };
+        if let Some(compaction) = &compaction {
+            eprintln!("Note: {}", compaction.note);
+        }
+        let question = sessions::SessionMessage::question(&prompt);
+        let Some(answer) = api::process_response_stream(&adapter_config, &messages, &render_options).await else {
+            std::process::exit(1);
+        };
+
+        // Record the exchange, and any summary so it isn't made again
+        let mut exchange: Vec<_> = compaction.and_then(|compaction| compaction.summary)
+            .map(|(summary, replaced)| session.summary(summary, replaced))
+            .into_iter()
+            .collect();
+        exchange.push(question);
+        exchange.push(sessions::SessionMessage::answer(answer, &adapter_name, adapter_config.model()));
+        if let Err(e) = session_store.save(&session, exchange) {
+            eprintln!("Warning: could not save session {}: {}", session.name, e);
+        }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,223 @@
+//! Generation settings given on the command line, layered over the selected
+//! adapter's config before the API client is built.
+
+use clap::ArgMatches;
+use std::error::Error;
+use crate::config::{AdapterConfig, WireApi};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GenerationOverrides {
+    pub model: Option<String>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub max_tokens: Option<u32>,
+    pub seed: Option<u64>,
+    pub stop: Option<Vec<String>>,
+    pub system: Option<String>,
+}
+
+/// What a provider accepts. `None` means the option is not supported.
+struct Capabilities {
+    model: bool,
+    temperature: Option<f32>,
+    top_p: bool,
+    seed: bool,
+    max_stop_sequences: Option<usize>,
+}
+
+impl GenerationOverrides {
+    pub fn from_matches(matches: &ArgMatches) -> Self {
+        GenerationOverrides {
+            model: matches.get_one::<String>("model").cloned(),
+            temperature: matches.get_one::<f32>("temperature").copied(),
+            top_p: matches.get_one::<f32>("top-p").copied(),
+            max_tokens: matches.get_one::<u32>("max-tokens").copied(),
+            seed: matches.get_one::<u64>("seed").copied(),
+            stop: matches.get_many::<String>("stop").map(|stop| stop.cloned().collect()),
+            system: matches.get_one::<String>("system").cloned(),
+        }
+    }
+
+    /// Checks every override against what the adapter's provider accepts,
+    /// then writes them into `adapter`.
+    pub fn apply(&self, adapter: &mut AdapterConfig) -> Result<(), Box<dyn Error>> {
+        self.validate(adapter)?;
+
+        match adapter {
+            AdapterConfig::OpenAI(config) => {
+                set(&mut config.default_model, &self.model);
+                set(&mut config.temperature, &self.temperature);
+                set_option(&mut config.top_p, &self.top_p);
+                set(&mut config.max_tokens, &self.max_tokens);
+                set_option(&mut config.seed, &self.seed);
+                set_option(&mut config.stop, &self.stop);
+                set_option(&mut config.system_prompt, &self.system);
+            },
+            AdapterConfig::AzureOpenAI(config) => {
+                set(&mut config.temperature, &self.temperature);
+                set_option(&mut config.top_p, &self.top_p);
+                set(&mut config.max_tokens, &self.max_tokens);
+                set_option(&mut config.seed, &self.seed);
+                set_option(&mut config.stop, &self.stop);
+                set_option(&mut config.system_prompt, &self.system);
+            },
+            AdapterConfig::Claude(config) => {
+                set(&mut config.default_model, &self.model);
+                set(&mut config.temperature, &self.temperature);
+                set(&mut config.max_tokens, &self.max_tokens);
+                set_option(&mut config.stop, &self.stop);
+                set_option(&mut config.system_prompt, &self.system);
+            },
+            AdapterConfig::Zhipu(config) => {
+                set(&mut config.default_model, &self.model);
+                set(&mut config.temperature, &self.temperature);
+                set(&mut config.max_tokens, &self.max_tokens);
+                set_option(&mut config.stop, &self.stop);
+                set_option(&mut config.system_prompt, &self.system);
+            },
+            AdapterConfig::Gemini(config) => {
+                set(&mut config.default_model, &self.model);
+                set(&mut config.temperature, &self.temperature);
+                set_option(&mut config.top_p, &self.top_p);
+                set(&mut config.max_tokens, &self.max_tokens);
+                set_option(&mut config.seed, &self.seed);
+                set_option(&mut config.stop, &self.stop);
+                set_option(&mut config.system_prompt, &self.system);
+            },
+            AdapterConfig::Ollama(config) => {
+                set(&mut config.default_model, &self.model);
+                set_option(&mut config.temperature, &self.temperature);
+                set_option(&mut config.top_p, &self.top_p);
+                set_option(&mut config.max_tokens, &self.max_tokens);
+                set_option(&mut config.seed, &self.seed);
+                set_option(&mut config.stop, &self.stop);
+                set_option(&mut config.system_prompt, &self.system);
+            },
+        }
+        Ok(())
+    }
+
+    fn validate(&self, adapter: &AdapterConfig) -> Result<(), Box<dyn Error>> {
+        let name = adapter.kind();
+        let capabilities = capabilities(adapter);
+        let unsupported = |option: &str| -> Box<dyn Error> {
+            format!("{} is not supported by the {} adapter", option, name).into()
+        };
+
+        if self.model.is_some() && !capabilities.model {
+            return Err(match adapter {
+                AdapterConfig::AzureOpenAI(_) => "--model is not supported by the AzureOpenAI adapter; \
+                    Azure selects the model through `deployment`".into(),
+                _ => unsupported("--model"),
+            });
+        }
+        if let Some(temperature) = self.temperature {
+            let max = capabilities.temperature.ok_or_else(|| unsupported("--temperature"))?;
+            if !(0.0..=max).contains(&temperature) {
+                return Err(format!("--temperature must be between 0 and {} for the {} adapter, got {}", max, name, temperature).into());
+            }
+        }
+        if let Some(top_p) = self.top_p {
+            if !capabilities.top_p {
+                return Err(unsupported("--top-p"));
+            }
+            if !(0.0..=1.0).contains(&top_p) {
+                return Err(format!("--top-p must be between 0 and 1, got {}", top_p).into());
+            }
+        }
+        if self.max_tokens == Some(0) {
+            return Err("--max-tokens must be at least 1".into());
+        }
+        if self.seed.is_some() && !capabilities.seed {
+            return Err(unsupported("--seed"));
+        }
+        if let Some(stop) = &self.stop {
+            let max = capabilities.max_stop_sequences.ok_or_else(|| unsupported("--stop"))?;
+            if stop.len() > max {
+                return Err(format!("The {} adapter accepts at most {} --stop sequence(s), got {}", name, max, stop.len()).into());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The highest temperature the adapter's provider accepts, if it takes one.
+pub fn max_temperature(adapter: &AdapterConfig) -> Option<f32> {
+    capabilities(adapter).temperature
+}
+
+fn capabilities(adapter: &AdapterConfig) -> Capabilities {
+    match adapter {
+        AdapterConfig::OpenAI(config) => match config.wire_api.unwrap_or_default() {
+            WireApi::ChatCompletions => Capabilities { model: true, temperature: Some(2.0), top_p: true, seed: true, max_stop_sequences: Some(4) },
+            WireApi::Responses => Capabilities { model: true, temperature: Some(2.0), top_p: true, seed: false, max_stop_sequences: None },
+        },
+        AdapterConfig::AzureOpenAI(_) => Capabilities { model: false, temperature: Some(2.0), top_p: true, seed: true, max_stop_sequences: Some(4) },
+        AdapterConfig::Claude(_) => Capabilities { model: true, temperature: Some(1.0), top_p: false, seed: false, max_stop_sequences: Some(usize::MAX) },
+        AdapterConfig::Zhipu(_) => Capabilities { model: true, temperature: Some(1.0), top_p: false, seed: false, max_stop_sequences: Some(1) },
+        AdapterConfig::Gemini(_) => Capabilities { model: true, temperature: Some(2.0), top_p: true, seed: true, max_stop_sequences: Some(5) },
+        AdapterConfig::Ollama(_) => Capabilities { model: true, temperature: Some(2.0), top_p: true, seed: true, max_stop_sequences: Some(usize::MAX) },
+    }
+}
+
+fn set<T: Clone>(field: &mut T, value: &Option<T>) {
+    if let Some(value) = value {
+        *field = value.clone();
+    }
+}
+
+fn set_option<T: Clone>(field: &mut Option<T>, value: &Option<T>) {
+    if value.is_some() {
+        *field = value.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli;
+    use crate::config::{ClaudeConfig, OpenAIConfig};
+
+    #[test]
+    fn test_apply_overrides() {
+        let matches = cli::build_cli().get_matches_from([
+            "ruskgpt", "--model", "gpt-4o-mini", "--temperature", "0.2", "--seed", "42",
+            "--stop", "END", "--stop", "STOP", "--system", "Answer in French.", "hi",
+        ]);
+        let overrides = GenerationOverrides::from_matches(&matches);
+
+        let mut adapter = AdapterConfig::OpenAI(OpenAIConfig { default_model: "gpt-4o".to_string(), ..Default::default() });
+        overrides.apply(&mut adapter).unwrap();
+        let AdapterConfig::OpenAI(config) = adapter else { unreachable!() };
+        assert_eq!(config.default_model, "gpt-4o-mini");
+        assert_eq!(config.temperature, 0.2);
+        assert_eq!(config.seed, Some(42));
+        assert_eq!(config.stop, Some(vec!["END".to_string(), "STOP".to_string()]));
+        assert_eq!(config.system_prompt.as_deref(), Some("Answer in French."));
+
+        // The same options are accepted after `chat`
+        let matches = cli::build_cli().get_matches_from(["ruskgpt", "chat", "--model", "gpt-4o", "--stop", "END"]);
+        let Some(("chat", chat_matches)) = matches.subcommand() else { unreachable!() };
+        let overrides = GenerationOverrides::from_matches(chat_matches);
+        assert_eq!(overrides.model.as_deref(), Some("gpt-4o"));
+        assert_eq!(overrides.stop, Some(vec!["END".to_string()]));
+    }
+
+    #[test]
+    fn test_reject_unsupported_overrides() {
+        let mut claude = AdapterConfig::Claude(ClaudeConfig::default());
+
+        let top_p = GenerationOverrides { top_p: Some(0.9), ..Default::default() };
+        assert_eq!(top_p.apply(&mut claude).unwrap_err().to_string(), "--top-p is not supported by the Claude adapter");
+
+        let temperature = GenerationOverrides { temperature: Some(1.5), ..Default::default() };
+        assert_eq!(
+            temperature.apply(&mut claude).unwrap_err().to_string(),
+            "--temperature must be between 0 and 1 for the Claude adapter, got 1.5"
+        );
+
+        let stop = GenerationOverrides { stop: Some(vec!["a".into(); 5]), ..Default::default() };
+        let mut openai = AdapterConfig::OpenAI(OpenAIConfig::default());
+        assert!(stop.apply(&mut openai).is_err());
+    }
+}
@@ -0,0 +1,167 @@
+//! Where ruskgpt keeps its files, following the XDG Base Directory spec:
+//! config in `$XDG_CONFIG_HOME/ruskgpt`, sessions and abilities in
+//! `$XDG_DATA_HOME/ruskgpt`, caches in `$XDG_CACHE_HOME/ruskgpt` and logs in
+//! `$XDG_STATE_HOME/ruskgpt`. Windows uses the matching known folders.
+
+use dirs_next::home_dir;
+use log::{info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::config_layers;
+
+const APP_DIR: &str = "ruskgpt";
+pub const CONFIG_FILE: &str = "config.toml";
+
+/// The base directories, resolved once so tests can build them from a fake
+/// environment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Paths {
+    pub config_dir: PathBuf,
+    pub data_dir: PathBuf,
+    pub cache_dir: PathBuf,
+    pub state_dir: PathBuf,
+    /// `~/.ruskgpt`, used before ruskgpt followed the XDG spec.
+    pub legacy_dir: PathBuf,
+}
+
+impl Paths {
+    pub fn new() -> Self {
+        Self::from_env(|name| std::env::var(name).ok(), home_dir().unwrap_or_default())
+    }
+
+    fn from_env(var: impl Fn(&str) -> Option<String>, home: PathBuf) -> Self {
+        // Relative values are invalid per the spec and are ignored
+        let base = |name: &str, unix_default: &str, windows_default: Option<PathBuf>| {
+            var(name)
+                .map(PathBuf::from)
+                .filter(|path| path.is_absolute())
+                .or(if cfg!(windows) { windows_default } else { None })
+                .unwrap_or_else(|| home.join(unix_default))
+                .join(APP_DIR)
+        };
+        Paths {
+            config_dir: base("XDG_CONFIG_HOME", ".config", dirs_next::config_dir()),
+            data_dir: base("XDG_DATA_HOME", ".local/share", dirs_next::data_dir()),
+            cache_dir: base("XDG_CACHE_HOME", ".cache", dirs_next::cache_dir()),
+            state_dir: base("XDG_STATE_HOME", ".local/state", dirs_next::data_local_dir()),
+            legacy_dir: home.join(".ruskgpt"),
+        }
+    }
+
+    pub fn config_file(&self) -> PathBuf {
+        self.config_dir.join(CONFIG_FILE)
+    }
+
+    pub fn sessions_dir(&self) -> PathBuf {
+        self.data_dir.join("sessions")
+    }
+
+    pub fn abilities_dir(&self) -> PathBuf {
+        self.data_dir.join("abilities")
+    }
+
+    pub fn log_dir(&self) -> PathBuf {
+        self.state_dir.join("logs")
+    }
+
+    /// `~/.ruskgpt/config.toml`, if it is there and there is no config in the
+    /// XDG config directory to take its place.
+    pub fn legacy_config(&self) -> Option<PathBuf> {
+        let legacy = self.legacy_dir.join(CONFIG_FILE);
+        Some(legacy).filter(|legacy| legacy.is_file() && !self.config_file().exists())
+    }
+
+    /// Moves `~/.ruskgpt/config.toml` to the XDG config directory if there is
+    /// no config there yet. Returns the config file to use: the new one, or
+    /// the legacy one if it could not be moved.
+    pub fn migrate_legacy_config(&self) -> Option<PathBuf> {
+        let legacy = self.legacy_config()?;
+        let current = self.config_file();
+
+        let moved = fs::create_dir_all(&self.config_dir)
+            .and_then(|_| fs::copy(&legacy, &current))
+            .and_then(|_| fs::rename(&legacy, legacy.with_extension("toml.migrated")));
+        match moved {
+            Ok(()) => {
+                info!("Moved {} to {}", legacy.display(), current.display());
+                eprintln!("Moved your config from {} to {}", legacy.display(), current.display());
+                Some(current)
+            },
+            Err(e) => {
+                warn!("Could not move {} to {}: {}", legacy.display(), current.display(), e);
+                Some(legacy)
+            },
+        }
+    }
+}
+
+/// Prints every location ruskgpt reads or writes, for `ruskgpt paths`.
+pub fn print_paths(paths: &Paths, config_file: &Path) {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let project_files = config_layers::project_config_paths(&cwd);
+
+    let mut rows: Vec<(&str, String)> = vec![
+        ("config file", config_file.display().to_string()),
+        ("config dir", paths.config_dir.display().to_string()),
+        ("system config", config_layers::system_config_path().map(|path| path.display().to_string()).unwrap_or_else(|| "(none)".to_string())),
+    ];
+    if project_files.is_empty() {
+        rows.push(("project config", "(none)".to_string()));
+    }
+    for path in &project_files {
+        rows.push(("project config", path.display().to_string()));
+    }
+    rows.extend([
+        ("data dir", paths.data_dir.display().to_string()),
+        ("sessions", paths.sessions_dir().display().to_string()),
+        ("abilities", paths.abilities_dir().display().to_string()),
+        ("cache dir", paths.cache_dir.display().to_string()),
+        ("state dir", paths.state_dir.display().to_string()),
+        ("logs", paths.log_dir().display().to_string()),
+    ]);
+
+    for (name, path) in rows {
+        println!("{:<15} {}", name, path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_xdg_dirs() {
+        let env = |name: &str| match name {
+            "XDG_CONFIG_HOME" => Some("/xdg/config".to_string()),
+            "XDG_CACHE_HOME" => Some("relative/cache".to_string()),
+            _ => None,
+        };
+        let paths = Paths::from_env(env, PathBuf::from("/home/me"));
+        assert_eq!(paths.config_file(), PathBuf::from("/xdg/config/ruskgpt/config.toml"));
+        assert_eq!(paths.sessions_dir(), PathBuf::from("/home/me/.local/share/ruskgpt/sessions"));
+        assert_eq!(paths.cache_dir, PathBuf::from("/home/me/.cache/ruskgpt"));
+        assert_eq!(paths.log_dir(), PathBuf::from("/home/me/.local/state/ruskgpt/logs"));
+        assert_eq!(paths.legacy_dir, PathBuf::from("/home/me/.ruskgpt"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_migrate_legacy_config() {
+        let home = std::env::temp_dir().join(format!("ruskgpt-paths-{}", std::process::id()));
+        fs::create_dir_all(home.join(".ruskgpt")).unwrap();
+        fs::write(home.join(".ruskgpt/config.toml"), "# legacy\n").unwrap();
+
+        let paths = Paths::from_env(|_| None, home.clone());
+        assert_eq!(paths.legacy_config(), Some(home.join(".ruskgpt/config.toml")));
+        assert!(!paths.config_file().exists());
+        assert_eq!(paths.migrate_legacy_config(), Some(home.join(".config/ruskgpt/config.toml")));
+        assert_eq!(fs::read_to_string(paths.config_file()).unwrap(), "# legacy\n");
+        assert!(home.join(".ruskgpt/config.toml.migrated").exists());
+        // Nothing left to move
+        assert_eq!(paths.legacy_config(), None);
+        assert_eq!(paths.migrate_legacy_config(), None);
+
+        fs::remove_dir_all(&home).unwrap();
+    }
+}
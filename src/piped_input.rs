@@ -0,0 +1,110 @@
+//! Text piped into ruskgpt. `cat error.log | ruskgpt "why did this fail?"`
+//! sends the log in a delimited block after the question, and `-` as the
+//! question reads the whole prompt from stdin.
+
+use std::error::Error;
+use std::io::{self, IsTerminal, Read};
+use crate::config::InputConfig;
+
+pub const DEFAULT_MAX_STDIN_BYTES: usize = 256 * 1024;
+
+/// Text read from stdin, cut off at the size limit.
+#[derive(Debug, Clone, PartialEq)]
+struct Piped {
+    text: String,
+    /// Set when there was more input than the limit allowed.
+    truncated: bool,
+}
+
+/// Reads at most `limit` bytes from `reader`, as it arrives.
+fn read_limited(reader: impl Read, limit: usize) -> Result<Piped, Box<dyn Error>> {
+    let mut bytes = Vec::new();
+    reader.take(limit as u64 + 1).read_to_end(&mut bytes)?;
+    let truncated = bytes.len() > limit;
+    bytes.truncate(limit);
+    if bytes.contains(&0) {
+        return Err("stdin looks like binary data, not text".into());
+    }
+    let mut text = String::from_utf8_lossy(&bytes).into_owned();
+    if truncated && text.ends_with(char::REPLACEMENT_CHARACTER) {
+        // The cut split a multi-byte character
+        text.pop();
+    }
+    Ok(Piped { text, truncated })
+}
+
+/// The question, followed by the piped text in a delimited block.
+fn combine(question: &str, piped: &Piped, limit: usize) -> String {
+    let mut prompt = format!("{}\n\n<stdin>\n{}\n", question.trim_end(), piped.text.trim_end_matches('\n'));
+    if piped.truncated {
+        prompt.push_str(&format!("[truncated after {} bytes]\n", limit));
+    }
+    prompt.push_str("</stdin>");
+    prompt
+}
+
+/// The prompt to send, from the question and whatever was piped in; `None`
+/// when there is neither. Stdin is only read when it is `piped`, or when the
+/// question is `-`.
+fn build_prompt(question: Option<&str>, stdin: impl Read, piped: bool, limit: usize) -> Result<Option<String>, Box<dyn Error>> {
+    if !piped && question != Some("-") {
+        return Ok(question.map(str::to_string));
+    }
+    let input = read_limited(stdin, limit)?;
+    if input.truncated {
+        eprintln!("Warning: stdin is longer than {} bytes; the rest was cut off. Raise input.max_stdin_bytes to send more.", limit);
+    }
+    let empty = input.text.trim().is_empty();
+
+    match question {
+        Some("-") if empty => Err("No prompt on stdin".into()),
+        Some("-") | None => Ok(Some(input.text).filter(|_| !empty)),
+        Some(question) if empty => Ok(Some(question.to_string())),
+        Some(question) => Ok(Some(combine(question, &input, limit))),
+    }
+}
+
+/// The prompt for a one-off question, reading stdin when it is not a terminal.
+pub fn read_prompt(question: Option<&str>, config: &InputConfig) -> Result<Option<String>, Box<dyn Error>> {
+    let stdin = io::stdin();
+    let piped = !stdin.is_terminal();
+    build_prompt(question, stdin.lock(), piped, config.max_stdin_bytes.unwrap_or(DEFAULT_MAX_STDIN_BYTES))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_limited() {
+        let piped = read_limited("short log\n".as_bytes(), 100).unwrap();
+        assert_eq!(piped, Piped { text: "short log\n".to_string(), truncated: false });
+
+        // The cut falls inside "é", which is dropped rather than mangled
+        let piped = read_limited("abcé".as_bytes(), 4).unwrap();
+        assert_eq!(piped, Piped { text: "abc".to_string(), truncated: true });
+
+        assert!(read_limited(&b"\x7fELF\0\0"[..], 100).is_err());
+    }
+
+    #[test]
+    fn test_build_prompt() {
+        let prompt = build_prompt(Some("why did this fail?"), "error: disk full\n".as_bytes(), true, 100).unwrap();
+        assert_eq!(prompt.as_deref(), Some("why did this fail?\n\n<stdin>\nerror: disk full\n</stdin>"));
+
+        let prompt = build_prompt(Some("summarize"), "0123456789".as_bytes(), true, 4).unwrap();
+        assert_eq!(prompt.as_deref(), Some("summarize\n\n<stdin>\n0123\n[truncated after 4 bytes]\n</stdin>"));
+
+        // `-` and a bare pipe both make stdin the whole prompt
+        let prompt = build_prompt(Some("-"), "explain monads".as_bytes(), false, 100).unwrap();
+        assert_eq!(prompt.as_deref(), Some("explain monads"));
+        let prompt = build_prompt(None, "explain monads".as_bytes(), true, 100).unwrap();
+        assert_eq!(prompt.as_deref(), Some("explain monads"));
+
+        // Nothing piped, or stdin is a terminal
+        assert_eq!(build_prompt(Some("hi"), "".as_bytes(), true, 100).unwrap().as_deref(), Some("hi"));
+        assert_eq!(build_prompt(Some("hi"), "ignored".as_bytes(), false, 100).unwrap().as_deref(), Some("hi"));
+        assert_eq!(build_prompt(None, "ignored".as_bytes(), false, 100).unwrap(), None);
+        assert!(build_prompt(Some("-"), " \n".as_bytes(), true, 100).is_err());
+    }
+}
@@ -1,25 +1,127 @@
+use reqwest::StatusCode;
 use serde_json::Value;
 use log::error;
+use crate::providers::ProviderError;
 
-pub fn process_openai_error_response(error_response: String) {
+pub fn map_openai_error(status: StatusCode, error_response: &str) -> ProviderError {
+    ProviderError::http(status, format!("OpenAI: {}", extract_error_message(error_response)))
+}
+
+/// Maps an `error` object sent inside a Responses API stream.
+pub fn map_openai_stream_error(error: &Value) -> ProviderError {
+    let message = error["message"].as_str().unwrap_or("Error occurred, but no message provided.");
+    match error["code"].as_str() {
+        Some(code) => ProviderError::new(format!("OpenAI: {}: {}", code, message)),
+        None => ProviderError::new(format!("OpenAI: {}", message)),
+    }
+}
+
+/// Azure wraps OpenAI errors with a `code`, and content filter rejections
+/// list the offending categories under `innererror.content_filter_result`.
+pub fn map_azure_error(status: StatusCode, error_response: &str) -> ProviderError {
+    let parsed: Value = serde_json::from_str(error_response).unwrap_or(Value::Null);
+    let error = &parsed["error"];
+    let message = extract_error_message(error_response);
+
+    let filtered = error["innererror"]["content_filter_result"].as_object()
+        .map(|results| results.iter()
+            .filter(|(_, result)| result["filtered"].as_bool().unwrap_or(false))
+            .map(|(category, result)| format!("{}: {}", category, result["severity"].as_str().unwrap_or("unknown")))
+            .collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    let mut text = match error["code"].as_str() {
+        Some(code) => format!("Azure OpenAI: {}: {}", code, message),
+        None => format!("Azure OpenAI: {}", message),
+    };
+    if !filtered.is_empty() {
+        text.push_str(&format!(" ({})", filtered.join(", ")));
+    }
+    ProviderError::http(status, text)
+}
+
+/// Azure stops a stream with `finish_reason: "content_filter"` when the
+/// answer itself trips the filter.
+pub fn map_azure_content_filter_finish() -> ProviderError {
+    ProviderError::new("Azure OpenAI: the response was stopped by the content filter")
+}
+
+pub fn map_claude_error(status: StatusCode, error_response: &str) -> ProviderError {
+    ProviderError::http(status, format!("Claude: {}", extract_error_message(error_response)))
+}
+
+/// Maps an `error` event received in the middle of a Claude stream.
+pub fn map_claude_stream_error(data: &str) -> ProviderError {
+    let parsed: Value = serde_json::from_str(data).unwrap_or(Value::Null);
+    match (parsed["error"]["type"].as_str(), parsed["error"]["message"].as_str()) {
+        (Some(kind), Some(message)) => ProviderError::new(format!("Claude: {}: {}", kind, message)),
+        _ => ProviderError::new(format!("Claude: {}", extract_error_message(data))),
+    }
+}
+
+pub fn map_zhipu_error(status: StatusCode, error_response: &str) -> ProviderError {
+    ProviderError::http(status, format!("Zhipu: {}", extract_error_message(error_response)))
+}
+
+pub fn map_gemini_error(status: StatusCode, error_response: &str) -> ProviderError {
+    let parsed: Value = serde_json::from_str(error_response).unwrap_or(Value::Null);
+    match parsed["error"]["status"].as_str() {
+        Some(kind) => ProviderError::http(status, format!("Gemini: {}: {}", kind, extract_error_message(error_response))),
+        None => ProviderError::http(status, format!("Gemini: {}", extract_error_message(error_response))),
+    }
+}
+
+/// Maps a blocked prompt or candidate to an error, listing the safety
+/// categories that were flagged.
+pub fn map_gemini_block(what: &str, reason: &str, safety_ratings: &Value) -> ProviderError {
+    let flagged = safety_ratings.as_array()
+        .map(|ratings| ratings.iter()
+            .filter(|rating| rating["blocked"].as_bool().unwrap_or(false)
+                || !matches!(rating["probability"].as_str(), Some("NEGLIGIBLE") | Some("LOW") | None))
+            .map(|rating| format!(
+                "{}: {}",
+                rating["category"].as_str().unwrap_or("UNKNOWN"),
+                rating["probability"].as_str().unwrap_or("UNKNOWN")
+            ))
+            .collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    if flagged.is_empty() {
+        ProviderError::new(format!("Gemini: {}: {}", what, reason))
+    } else {
+        ProviderError::new(format!("Gemini: {}: {} ({})", what, reason, flagged.join(", ")))
+    }
+}
+
+/// Ollama reports errors as `{"error": "..."}` with a plain string.
+pub fn map_ollama_error(status: StatusCode, error_response: &str) -> ProviderError {
+    let parsed: Value = serde_json::from_str(error_response).unwrap_or(Value::Null);
+    match parsed["error"].as_str() {
+        Some(message) => ProviderError::http(status, format!("Ollama: {}", message)),
+        None => ProviderError::http(status, format!("Ollama: {}", error_response.trim())),
+    }
+}
+
+/// Pulls `error.message` out of an error body. Every backend except Ollama
+/// nests its message there.
+fn extract_error_message(error_response: &str) -> String {
     // parse error response
-    let parsed: Value = match serde_json::from_str(error_response.as_str()) {
+    let parsed: Value = match serde_json::from_str(error_response) {
         Ok(value) => value,
         Err(e) => {
             error!("Failed to parse error response: {}", e);
-            println!("Received an error, but failed to parse the response.");
-            return;
+            return "Received an error, but failed to parse the response.".to_string();
         }
     };
 
-    // extract and print error message
+    // extract error message
     if let Some(error) = parsed.get("error") {
         if let Some(message) = error.get("message").and_then(Value::as_str) {
-            println!("Error: {}", message);
+            message.to_string()
         } else {
-            println!("Error occurred, but no message provided.");
+            "Error occurred, but no message provided.".to_string()
         }
     } else {
-        println!("Received an error, but the response format is unexpected.");
+        "Received an error, but the response format is unexpected.".to_string()
     }
 }
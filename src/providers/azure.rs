@@ -0,0 +1,117 @@
+use futures::StreamExt;
+use log::info;
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use crate::config::AzureOpenAIConfig;
+use crate::process_response;
+use super::openai::chat_completions_stream;
+use super::{ChatMessage, EventStream, Provider, ProviderError, StreamEvent};
+
+pub struct AzureOpenAIProvider {
+    config: AzureOpenAIConfig,
+}
+
+impl AzureOpenAIProvider {
+    pub fn new(config: AzureOpenAIConfig) -> Self {
+        AzureOpenAIProvider { config }
+    }
+
+    fn url(&self) -> String {
+        format!(
+            "{}/openai/deployments/{}/chat/completions?api-version={}",
+            self.config.base_url.trim_end_matches('/'),
+            self.config.deployment,
+            self.config.api_version
+        )
+    }
+}
+
+impl Provider for AzureOpenAIProvider {
+    fn name(&self) -> &'static str {
+        "Azure OpenAI"
+    }
+
+    fn build_request(&self, client: &Client, messages: &[ChatMessage]) -> Result<RequestBuilder, ProviderError> {
+        let url = self.url();
+        // The deployment selects the model, so there is no "model" field
+        let mut request_body = serde_json::json!({
+            "messages": messages,
+            "temperature": self.config.temperature,
+            "top_p": self.config.top_p.unwrap_or(1.0),
+            "max_tokens": self.config.max_tokens,
+            "stream": true,
+        });
+        if let Some(seed) = self.config.seed {
+            request_body["seed"] = serde_json::json!(seed);
+        }
+        if let Some(stop) = &self.config.stop {
+            request_body["stop"] = serde_json::json!(stop);
+        }
+
+        info!("Sending POST request to URL: {}", url);
+        info!("Request body: {}", request_body);
+
+        Ok(client.post(&url)
+            .header("Content-Type", "application/json")
+            .json(&request_body))
+    }
+
+    fn authenticate(&self, request: RequestBuilder) -> Result<RequestBuilder, ProviderError> {
+        Ok(request.header("api-key", &self.config.token))
+    }
+
+    fn parse_stream(&self, response: Response) -> EventStream {
+        let stream = chat_completions_stream(response).map(|event| match event {
+            Ok(StreamEvent::Done(Some(reason))) if reason == "content_filter" => {
+                Err(process_response::map_azure_content_filter_finish())
+            },
+            other => other,
+        });
+        Box::pin(stream)
+    }
+
+    fn map_error(&self, status: StatusCode, body: &str) -> ProviderError {
+        process_response::map_azure_error(status, body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_client::ApiClient;
+    use crate::config::AdapterConfig;
+    use crate::providers::mock_server;
+
+    fn test_config(base_url: String) -> AzureOpenAIConfig {
+        AzureOpenAIConfig {
+            base_url,
+            deployment: "gpt-4o-prod".to_string(),
+            api_version: "2024-06-01".to_string(),
+            token: "azure-key".to_string(),
+            temperature: 0.7,
+            top_p: None,
+            max_tokens: 64,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_content_filter_error() {
+        let body = r#"{"error":{"message":"The response was filtered","code":"content_filter","status":400,
+            "innererror":{"code":"ResponsibleAIPolicyViolation","content_filter_result":{
+            "hate":{"filtered":false,"severity":"safe"},"violence":{"filtered":true,"severity":"medium"}}}}}"#;
+        let (base_url, server) = mock_server::serve_once(400, "application/json", body).await;
+        let client = ApiClient::new(AdapterConfig::AzureOpenAI(test_config(base_url)));
+
+        let error = match client.stream_chat(&[ChatMessage::user("hi")]).await {
+            Err(e) => e,
+            Ok(_) => panic!("expected an error"),
+        };
+        assert_eq!(error.status, Some(400));
+        assert_eq!(error.message, "Azure OpenAI: content_filter: The response was filtered (violence: medium)");
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /openai/deployments/gpt-4o-prod/chat/completions?api-version=2024-06-01 "));
+        assert!(request.contains("api-key: azure-key"));
+        assert!(!request.to_lowercase().contains("authorization:"));
+    }
+}
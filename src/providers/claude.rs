@@ -0,0 +1,148 @@
+use futures::StreamExt;
+use log::{info, warn};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde_json::Value;
+use crate::config::ClaudeConfig;
+use crate::process_response;
+use super::{sse_events, ChatMessage, EventStream, Provider, ProviderError, Role, StreamEvent, Usage};
+
+pub struct ClaudeProvider {
+    config: ClaudeConfig,
+}
+
+impl ClaudeProvider {
+    pub fn new(config: ClaudeConfig) -> Self {
+        ClaudeProvider { config }
+    }
+}
+
+impl Provider for ClaudeProvider {
+    fn name(&self) -> &'static str {
+        "Claude"
+    }
+
+    fn build_request(&self, client: &Client, messages: &[ChatMessage]) -> Result<RequestBuilder, ProviderError> {
+        let url = format!("{}/messages", self.config.base_url);
+        // The Messages API takes the system prompt as a top level field
+        let system = messages.iter()
+            .filter(|message| message.role == Role::System)
+            .map(|message| message.content.as_str())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let messages: Vec<&ChatMessage> = messages.iter()
+            .filter(|message| message.role != Role::System)
+            .collect();
+        let mut request_body = serde_json::json!({
+            "model": self.config.default_model,
+            "max_tokens": self.config.max_tokens,
+            "temperature": self.config.temperature,
+            "messages": messages,
+            "stream": true,
+        });
+        if !system.is_empty() {
+            request_body["system"] = Value::String(system);
+        }
+        if let Some(stop) = &self.config.stop {
+            request_body["stop_sequences"] = serde_json::json!(stop);
+        }
+
+        info!("Sending POST request to URL: {}", url);
+        info!("Request body: {}", request_body);
+
+        Ok(client.post(&url)
+            .header("Content-Type", "application/json")
+            .header("anthropic-version", "2023-06-01")
+            .json(&request_body))
+    }
+
+    fn authenticate(&self, request: RequestBuilder) -> Result<RequestBuilder, ProviderError> {
+        Ok(request.header("x-api-key", &self.config.token))
+    }
+
+    fn parse_stream(&self, response: Response) -> EventStream {
+        let stream = sse_events(response).flat_map(|event| futures::stream::iter(match event {
+            Ok(event) => match claude_events(&event.event, &event.data) {
+                Ok(events) => events.into_iter().map(Ok).collect::<Vec<_>>(),
+                Err(e) => vec![Err(e)],
+            },
+            Err(e) => vec![Err(e)],
+        }));
+        Box::pin(stream)
+    }
+
+    fn map_error(&self, status: StatusCode, body: &str) -> ProviderError {
+        process_response::map_claude_error(status, body)
+    }
+}
+
+/// Maps one Messages API server-sent event to stream events.
+fn claude_events(event: &str, data: &str) -> Result<Vec<StreamEvent>, ProviderError> {
+    match event {
+        "ping" | "content_block_start" | "content_block_stop" | "message_stop" => Ok(Vec::new()),
+        "message_start" => {
+            let parsed: Value = serde_json::from_str(data)
+                .map_err(|e| ProviderError::new(format!("Claude: malformed message_start: {}", e)))?;
+            // Output tokens are counted again in the final message_delta
+            Ok(parsed["message"]["usage"]["input_tokens"].as_u64()
+                .map(|input_tokens| StreamEvent::Usage(Usage { input_tokens, output_tokens: 0 }))
+                .into_iter()
+                .collect())
+        },
+        "content_block_delta" => {
+            let parsed: Value = serde_json::from_str(data)
+                .map_err(|e| ProviderError::new(format!("Claude: malformed content_block_delta: {}", e)))?;
+            let delta = &parsed["delta"];
+            // Extended thinking arrives as `thinking_delta` blocks
+            if let Some(thinking) = delta["thinking"].as_str().filter(|text| !text.is_empty()) {
+                return Ok(vec![StreamEvent::Reasoning(thinking.to_string())]);
+            }
+            Ok(delta["text"].as_str()
+                .filter(|text| !text.is_empty())
+                .map(|text| StreamEvent::Text(text.to_string()))
+                .into_iter()
+                .collect())
+        },
+        "message_delta" => {
+            let parsed: Value = serde_json::from_str(data)
+                .map_err(|e| ProviderError::new(format!("Claude: malformed message_delta: {}", e)))?;
+            let mut events = Vec::new();
+            if let Some(output_tokens) = parsed["usage"]["output_tokens"].as_u64() {
+                events.push(StreamEvent::Usage(Usage { input_tokens: 0, output_tokens }));
+            }
+            if let Some(reason) = parsed["delta"]["stop_reason"].as_str() {
+                events.push(StreamEvent::Done(Some(reason.to_string())));
+            }
+            Ok(events)
+        },
+        "error" => Err(process_response::map_claude_stream_error(data)),
+        other => {
+            warn!("Ignoring unknown Claude event: {}", other);
+            Ok(Vec::new())
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_claude_events() {
+        let start = r#"{"type":"message_start","message":{"id":"msg_1","usage":{"input_tokens":25,"output_tokens":1}}}"#;
+        assert_eq!(claude_events("message_start", start).unwrap(), vec![StreamEvent::Usage(Usage { input_tokens: 25, output_tokens: 0 })]);
+
+        let delta = r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hi"}}"#;
+        assert_eq!(claude_events("content_block_delta", delta).unwrap(), vec![StreamEvent::Text("Hi".to_string())]);
+
+        let stop = r#"{"type":"message_delta","delta":{"stop_reason":"end_turn"},"usage":{"output_tokens":15}}"#;
+        assert_eq!(claude_events("message_delta", stop).unwrap(), vec![
+            StreamEvent::Usage(Usage { input_tokens: 0, output_tokens: 15 }),
+            StreamEvent::Done(Some("end_turn".to_string())),
+        ]);
+
+        assert_eq!(claude_events("ping", r#"{"type":"ping"}"#).unwrap(), vec![]);
+
+        let error = r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#;
+        assert_eq!(claude_events("error", error).unwrap_err().message, "Claude: overloaded_error: Overloaded");
+    }
+}
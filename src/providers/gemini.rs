@@ -0,0 +1,178 @@
+use futures::StreamExt;
+use log::info;
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde_json::Value;
+use crate::config::GeminiConfig;
+use crate::process_response;
+use super::{sse_events, ChatMessage, EventStream, Provider, ProviderError, Role, StreamEvent, Usage};
+
+pub struct GeminiProvider {
+    config: GeminiConfig,
+}
+
+impl GeminiProvider {
+    pub fn new(config: GeminiConfig) -> Self {
+        GeminiProvider { config }
+    }
+}
+
+impl Provider for GeminiProvider {
+    fn name(&self) -> &'static str {
+        "Gemini"
+    }
+
+    fn build_request(&self, client: &Client, messages: &[ChatMessage]) -> Result<RequestBuilder, ProviderError> {
+        let url = format!(
+            "{}/models/{}:streamGenerateContent?alt=sse",
+            self.config.base_url.trim_end_matches('/'),
+            self.config.default_model
+        );
+
+        let system = messages.iter()
+            .filter(|message| message.role == Role::System)
+            .map(|message| serde_json::json!({ "text": message.content }))
+            .collect::<Vec<_>>();
+        let contents = messages.iter()
+            .filter(|message| message.role != Role::System)
+            .map(|message| serde_json::json!({
+                // Gemini calls the assistant "model"
+                "role": if message.role == Role::Assistant { "model" } else { "user" },
+                "parts": [{ "text": message.content }],
+            }))
+            .collect::<Vec<_>>();
+
+        let mut generation_config = serde_json::json!({
+            "temperature": self.config.temperature,
+            "maxOutputTokens": self.config.max_tokens,
+        });
+        if let Some(top_p) = self.config.top_p {
+            generation_config["topP"] = serde_json::json!(top_p);
+        }
+        if let Some(seed) = self.config.seed {
+            generation_config["seed"] = serde_json::json!(seed);
+        }
+        if let Some(stop) = &self.config.stop {
+            generation_config["stopSequences"] = serde_json::json!(stop);
+        }
+
+        let mut request_body = serde_json::json!({
+            "contents": contents,
+            "generationConfig": generation_config,
+        });
+        if !system.is_empty() {
+            request_body["systemInstruction"] = serde_json::json!({ "parts": system });
+        }
+
+        info!("Sending POST request to URL: {}", url);
+        info!("Request body: {}", request_body);
+
+        Ok(client.post(&url)
+            .header("Content-Type", "application/json")
+            .json(&request_body))
+    }
+
+    fn authenticate(&self, request: RequestBuilder) -> Result<RequestBuilder, ProviderError> {
+        Ok(request.header("x-goog-api-key", &self.config.token))
+    }
+
+    fn parse_stream(&self, response: Response) -> EventStream {
+        let stream = sse_events(response).flat_map(|event| {
+            futures::stream::iter(match event {
+                Ok(event) => match serde_json::from_str::<Value>(&event.data) {
+                    Ok(chunk) => match gemini_events(&chunk) {
+                        Ok(events) => events.into_iter().map(Ok).collect::<Vec<_>>(),
+                        Err(e) => vec![Err(e)],
+                    },
+                    Err(e) => vec![Err(ProviderError::new(format!("Gemini: malformed stream chunk: {}", e)))],
+                },
+                Err(e) => vec![Err(e)],
+            })
+        });
+        Box::pin(stream)
+    }
+
+    fn map_error(&self, status: StatusCode, body: &str) -> ProviderError {
+        process_response::map_gemini_error(status, body)
+    }
+}
+
+/// Extracts the events carried by one `GenerateContentResponse` chunk. A
+/// blocked prompt or a candidate stopped for safety or recitation is an
+/// error rather than an empty answer.
+fn gemini_events(chunk: &Value) -> Result<Vec<StreamEvent>, ProviderError> {
+    if let Some(reason) = chunk["promptFeedback"]["blockReason"].as_str() {
+        return Err(process_response::map_gemini_block(
+            "prompt blocked",
+            reason,
+            &chunk["promptFeedback"]["safetyRatings"],
+        ));
+    }
+
+    let mut events = Vec::new();
+    let candidate = &chunk["candidates"][0];
+    if let Some(parts) = candidate["content"]["parts"].as_array() {
+        for text in parts.iter().filter_map(|part| part["text"].as_str()) {
+            if !text.is_empty() {
+                events.push(StreamEvent::Text(text.to_string()));
+            }
+        }
+    }
+
+    match candidate["finishReason"].as_str() {
+        None | Some("FINISH_REASON_UNSPECIFIED") => {},
+        Some(reason @ ("STOP" | "MAX_TOKENS")) => {
+            // Every chunk repeats the running counts, so only the last one is kept
+            if let Some(usage) = Usage::from_json(&chunk["usageMetadata"], "promptTokenCount", "candidatesTokenCount") {
+                events.push(StreamEvent::Usage(usage));
+            }
+            events.push(StreamEvent::Done(Some(reason.to_string())));
+        },
+        Some(reason) => {
+            return Err(process_response::map_gemini_block(
+                "response stopped",
+                reason,
+                &candidate["safetyRatings"],
+            ));
+        },
+    }
+
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gemini_events() {
+        let chunk = serde_json::json!({
+            "candidates": [{ "content": { "role": "model", "parts": [{ "text": "Hello" }] } }]
+        });
+        assert_eq!(gemini_events(&chunk).unwrap(), vec![StreamEvent::Text("Hello".to_string())]);
+
+        let last = serde_json::json!({
+            "candidates": [{ "content": { "parts": [{ "text": "!" }] }, "finishReason": "STOP" }]
+        });
+        assert_eq!(gemini_events(&last).unwrap(), vec![
+            StreamEvent::Text("!".to_string()),
+            StreamEvent::Done(Some("STOP".to_string())),
+        ]);
+
+        let unsafe_candidate = serde_json::json!({
+            "candidates": [{
+                "finishReason": "SAFETY",
+                "safetyRatings": [
+                    { "category": "HARM_CATEGORY_HARASSMENT", "probability": "NEGLIGIBLE" },
+                    { "category": "HARM_CATEGORY_DANGEROUS_CONTENT", "probability": "HIGH", "blocked": true }
+                ]
+            }]
+        });
+        assert_eq!(
+            gemini_events(&unsafe_candidate).unwrap_err().message,
+            "Gemini: response stopped: SAFETY (HARM_CATEGORY_DANGEROUS_CONTENT: HIGH)"
+        );
+
+        let blocked_prompt = serde_json::json!({ "promptFeedback": { "blockReason": "OTHER" } });
+        assert_eq!(gemini_events(&blocked_prompt).unwrap_err().message, "Gemini: prompt blocked: OTHER");
+    }
+}
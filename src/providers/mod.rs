@@ -0,0 +1,181 @@
+//! LLM backends. Every vendor lives in its own module and implements
+//! [`Provider`], so `ApiClient` can drive all of them the same way.
+
+mod azure;
+mod claude;
+mod gemini;
+mod ollama;
+mod openai;
+mod openai_responses;
+mod zhipu;
+#[cfg(test)]
+pub(crate) mod mock_server;
+
+use futures::{Stream, StreamExt};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::pin::Pin;
+use crate::config::AdapterConfig;
+use crate::sse::{SseDecoder, SseEvent};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ChatMessage {
+    pub role: Role,
+    pub content: String,
+}
+
+impl ChatMessage {
+    pub fn new(role: Role, content: impl Into<String>) -> Self {
+        ChatMessage { role, content: content.into() }
+    }
+
+    pub fn system(content: impl Into<String>) -> Self {
+        Self::new(Role::System, content)
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Self::new(Role::User, content)
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self::new(Role::Assistant, content)
+    }
+}
+
+/// A single event decoded from a provider's response stream.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamEvent {
+    /// A piece of the assistant's answer.
+    Text(String),
+    /// A piece of the model's chain of thought, streamed before the answer
+    /// by reasoning models. It is shown to the user but never sent back.
+    Reasoning(String),
+    /// The provider finished the answer, with its finish reason if it sent one.
+    Done(Option<String>),
+    /// Token counts for the request. Some providers report input and output
+    /// in separate events; they add up.
+    Usage(Usage),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Usage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+impl Usage {
+    /// Reads a provider's usage object, given the names of its input and
+    /// output counts. `None` if it has neither.
+    pub(super) fn from_json(usage: &Value, input: &str, output: &str) -> Option<Usage> {
+        let (input_tokens, output_tokens) = (usage[input].as_u64(), usage[output].as_u64());
+        if input_tokens.is_none() && output_tokens.is_none() {
+            return None;
+        }
+        Some(Usage { input_tokens: input_tokens.unwrap_or(0), output_tokens: output_tokens.unwrap_or(0) })
+    }
+}
+
+impl std::ops::AddAssign for Usage {
+    fn add_assign(&mut self, other: Usage) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProviderError {
+    pub status: Option<u16>,
+    pub message: String,
+}
+
+impl ProviderError {
+    pub fn new(message: impl Into<String>) -> Self {
+        ProviderError { status: None, message: message.into() }
+    }
+
+    pub fn http(status: StatusCode, message: impl Into<String>) -> Self {
+        ProviderError { status: Some(status.as_u16()), message: message.into() }
+    }
+}
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.status {
+            Some(status) => write!(f, "{} (HTTP {})", self.message, status),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl Error for ProviderError {}
+
+impl From<reqwest::Error> for ProviderError {
+    fn from(e: reqwest::Error) -> Self {
+        ProviderError { status: e.status().map(|s| s.as_u16()), message: e.to_string() }
+    }
+}
+
+pub type EventStream = Pin<Box<dyn Stream<Item = Result<StreamEvent, ProviderError>> + Send>>;
+
+pub trait Provider: Send + Sync {
+    /// Human readable vendor name, used in logs and error messages.
+    fn name(&self) -> &'static str;
+
+    /// Builds the HTTP request for `messages`, without credentials.
+    fn build_request(&self, client: &Client, messages: &[ChatMessage]) -> Result<RequestBuilder, ProviderError>;
+
+    /// Attaches credentials to a request returned by `build_request`.
+    fn authenticate(&self, request: RequestBuilder) -> Result<RequestBuilder, ProviderError>;
+
+    /// Turns a successful response into a stream of events.
+    fn parse_stream(&self, response: Response) -> EventStream;
+
+    /// Maps a non-success response into an error the user can act on.
+    fn map_error(&self, status: StatusCode, body: &str) -> ProviderError;
+}
+
+pub fn from_config(config: AdapterConfig) -> Box<dyn Provider> {
+    match config {
+        AdapterConfig::OpenAI(cfg) => Box::new(openai::OpenAIProvider::new(cfg)),
+        AdapterConfig::Claude(cfg) => Box::new(claude::ClaudeProvider::new(cfg)),
+        AdapterConfig::Zhipu(cfg) => Box::new(zhipu::ZhipuProvider::new(cfg)),
+        AdapterConfig::Gemini(cfg) => Box::new(gemini::GeminiProvider::new(cfg)),
+        AdapterConfig::Ollama(cfg) => Box::new(ollama::OllamaProvider::new(cfg)),
+        AdapterConfig::AzureOpenAI(cfg) => Box::new(azure::AzureOpenAIProvider::new(cfg)),
+    }
+}
+
+/// Decodes a `text/event-stream` response body into server-sent events.
+pub(super) fn sse_events(response: Response) -> impl Stream<Item = Result<SseEvent, ProviderError>> {
+    let state = (response.bytes_stream(), SseDecoder::new(), VecDeque::new(), false);
+    futures::stream::unfold(state, |(mut bytes, mut decoder, mut events, mut finished)| async move {
+        loop {
+            if let Some(event) = events.pop_front() {
+                return Some((Ok(event), (bytes, decoder, events, finished)));
+            }
+            if finished {
+                return None;
+            }
+            match bytes.next().await {
+                Some(Ok(chunk)) => events.extend(decoder.feed(&chunk)),
+                Some(Err(e)) => return Some((Err(e.into()), (bytes, decoder, events, finished))),
+                None => {
+                    finished = true;
+                    events.extend(decoder.finish());
+                },
+            }
+        }
+    })
+}
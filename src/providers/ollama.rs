@@ -0,0 +1,203 @@
+use futures::{Stream, StreamExt};
+use log::info;
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde_json::Value;
+use std::collections::VecDeque;
+use crate::config::OllamaConfig;
+use crate::process_response;
+use super::{ChatMessage, EventStream, Provider, ProviderError, StreamEvent, Usage};
+
+pub struct OllamaProvider {
+    config: OllamaConfig,
+}
+
+impl OllamaProvider {
+    pub fn new(config: OllamaConfig) -> Self {
+        OllamaProvider { config }
+    }
+}
+
+impl Provider for OllamaProvider {
+    fn name(&self) -> &'static str {
+        "Ollama"
+    }
+
+    fn build_request(&self, client: &Client, messages: &[ChatMessage]) -> Result<RequestBuilder, ProviderError> {
+        let url = format!("{}/api/chat", self.config.base_url.trim_end_matches('/'));
+
+        let mut options = serde_json::Map::new();
+        if let Some(temperature) = self.config.temperature {
+            options.insert("temperature".to_string(), serde_json::json!(temperature));
+        }
+        if let Some(top_p) = self.config.top_p {
+            options.insert("top_p".to_string(), serde_json::json!(top_p));
+        }
+        if let Some(max_tokens) = self.config.max_tokens {
+            options.insert("num_predict".to_string(), serde_json::json!(max_tokens));
+        }
+        if let Some(num_ctx) = self.config.num_ctx {
+            options.insert("num_ctx".to_string(), serde_json::json!(num_ctx));
+        }
+        if let Some(seed) = self.config.seed {
+            options.insert("seed".to_string(), serde_json::json!(seed));
+        }
+        if let Some(stop) = &self.config.stop {
+            options.insert("stop".to_string(), serde_json::json!(stop));
+        }
+
+        let mut request_body = serde_json::json!({
+            "model": self.config.default_model,
+            "messages": messages,
+            "stream": true,
+            "options": options,
+        });
+        if let Some(keep_alive) = &self.config.keep_alive {
+            request_body["keep_alive"] = Value::String(keep_alive.clone());
+        }
+        if let Some(format) = &self.config.format {
+            request_body["format"] = Value::String(format.clone());
+        }
+
+        info!("Sending POST request to URL: {}", url);
+        info!("Request body: {}", request_body);
+
+        Ok(client.post(&url)
+            .header("Content-Type", "application/json")
+            .json(&request_body))
+    }
+
+    fn authenticate(&self, request: RequestBuilder) -> Result<RequestBuilder, ProviderError> {
+        // A local Ollama needs no credentials
+        match self.config.token.as_deref() {
+            Some(token) if !token.is_empty() => Ok(request.bearer_auth(token)),
+            _ => Ok(request),
+        }
+    }
+
+    fn parse_stream(&self, response: Response) -> EventStream {
+        let stream = ndjson_lines(response).flat_map(|line| {
+            futures::stream::iter(match line {
+                Ok(line) => match serde_json::from_str::<Value>(&line) {
+                    Ok(chunk) => match ollama_events(&chunk) {
+                        Ok(events) => events.into_iter().map(Ok).collect::<Vec<_>>(),
+                        Err(e) => vec![Err(e)],
+                    },
+                    Err(e) => vec![Err(ProviderError::new(format!("Ollama: malformed stream line: {}", e)))],
+                },
+                Err(e) => vec![Err(e)],
+            })
+        });
+        Box::pin(stream)
+    }
+
+    fn map_error(&self, status: StatusCode, body: &str) -> ProviderError {
+        process_response::map_ollama_error(status, body)
+    }
+}
+
+/// Extracts the events carried by one line of an `/api/chat` stream.
+fn ollama_events(chunk: &Value) -> Result<Vec<StreamEvent>, ProviderError> {
+    if let Some(error) = chunk["error"].as_str() {
+        return Err(ProviderError::new(format!("Ollama: {}", error)));
+    }
+
+    let mut events = Vec::new();
+    if let Some(thinking) = chunk["message"]["thinking"].as_str() {
+        if !thinking.is_empty() {
+            events.push(StreamEvent::Reasoning(thinking.to_string()));
+        }
+    }
+    if let Some(content) = chunk["message"]["content"].as_str() {
+        if !content.is_empty() {
+            events.push(StreamEvent::Text(content.to_string()));
+        }
+    }
+    if chunk["done"].as_bool().unwrap_or(false) {
+        if let Some(usage) = Usage::from_json(chunk, "prompt_eval_count", "eval_count") {
+            events.push(StreamEvent::Usage(usage));
+        }
+        events.push(StreamEvent::Done(chunk["done_reason"].as_str().map(str::to_string)));
+    }
+    Ok(events)
+}
+
+/// Splits a newline-delimited JSON body into lines, buffering across
+/// network chunks so a line split between two reads is kept whole.
+fn ndjson_lines(response: Response) -> impl Stream<Item = Result<String, ProviderError>> {
+    let state = (response.bytes_stream(), Vec::<u8>::new(), VecDeque::new(), false);
+    futures::stream::unfold(state, |(mut bytes, mut buffer, mut lines, mut finished)| async move {
+        loop {
+            if let Some(line) = lines.pop_front() {
+                return Some((Ok(line), (bytes, buffer, lines, finished)));
+            }
+            if finished {
+                return None;
+            }
+            match bytes.next().await {
+                Some(Ok(chunk)) => {
+                    buffer.extend_from_slice(&chunk);
+                    while let Some(end) = buffer.iter().position(|&b| b == b'\n') {
+                        let raw: Vec<u8> = buffer.drain(..=end).collect();
+                        let line = String::from_utf8_lossy(&raw).trim().to_string();
+                        if !line.is_empty() {
+                            lines.push_back(line);
+                        }
+                    }
+                },
+                Some(Err(e)) => return Some((Err(e.into()), (bytes, buffer, lines, finished))),
+                None => {
+                    // The last line may not end with a newline
+                    finished = true;
+                    let line = String::from_utf8_lossy(&buffer).trim().to_string();
+                    buffer.clear();
+                    if !line.is_empty() {
+                        lines.push_back(line);
+                    }
+                },
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{render_stream, RenderOptions};
+    use crate::api_client::ApiClient;
+    use crate::config::AdapterConfig;
+    use crate::providers::mock_server;
+
+    #[tokio::test]
+    async fn test_ndjson_stream_without_token() {
+        let body = concat!(
+            "{\"message\":{\"role\":\"assistant\",\"content\":\"Hel\"},\"done\":false}\n",
+            "{\"message\":{\"role\":\"assistant\",\"content\":\"lo\"},\"done\":false}\n",
+            "{\"message\":{\"role\":\"assistant\",\"content\":\"\"},\"done\":true,\"done_reason\":\"stop\"}",
+        );
+        let (base_url, server) = mock_server::serve_once(200, "application/x-ndjson", body).await;
+        let client = ApiClient::new(AdapterConfig::Ollama(OllamaConfig {
+            base_url,
+            default_model: "llama3".to_string(),
+            temperature: Some(0.2),
+            num_ctx: Some(8192),
+            keep_alive: Some("10m".to_string()),
+            ..Default::default()
+        }));
+
+        let stream = client.stream_chat(&[ChatMessage::user("hi")]).await.unwrap();
+        assert_eq!(render_stream(stream, &RenderOptions::default()).await.text, "Hello");
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /api/chat "));
+        assert!(!request.to_lowercase().contains("authorization:"));
+        let body: Value = serde_json::from_str(request.split("\r\n\r\n").nth(1).unwrap()).unwrap();
+        assert_eq!(body["options"]["num_ctx"], 8192);
+        assert_eq!(body["keep_alive"], "10m");
+    }
+
+    #[test]
+    fn test_ollama_error_line() {
+        let chunk = serde_json::json!({ "error": "model \"llama9\" not found" });
+        assert_eq!(ollama_events(&chunk).unwrap_err().message, "Ollama: model \"llama9\" not found");
+    }
+}
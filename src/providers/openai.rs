@@ -0,0 +1,184 @@
+use futures::StreamExt;
+use log::info;
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde_json::Value;
+use crate::config::{OpenAIConfig, WireApi};
+use crate::process_response;
+use super::{openai_responses, sse_events, ChatMessage, EventStream, Provider, ProviderError, StreamEvent, Usage};
+
+pub struct OpenAIProvider {
+    config: OpenAIConfig,
+}
+
+impl OpenAIProvider {
+    pub fn new(config: OpenAIConfig) -> Self {
+        OpenAIProvider { config }
+    }
+}
+
+impl Provider for OpenAIProvider {
+    fn name(&self) -> &'static str {
+        "OpenAI"
+    }
+
+    fn build_request(&self, client: &Client, messages: &[ChatMessage]) -> Result<RequestBuilder, ProviderError> {
+        let (url, request_body) = match self.config.wire_api.unwrap_or_default() {
+            WireApi::ChatCompletions => {
+                let mut request_body = serde_json::json!({
+                    "model": self.config.default_model,
+                    "messages": messages,
+                    "temperature": self.config.temperature,
+                    "top_p": self.config.top_p.unwrap_or(1.0),
+                    "max_tokens": self.config.max_tokens,
+                    "stream": true,
+                    "stream_options": { "include_usage": true },
+                });
+                if let Some(seed) = self.config.seed {
+                    request_body["seed"] = serde_json::json!(seed);
+                }
+                if let Some(stop) = &self.config.stop {
+                    request_body["stop"] = serde_json::json!(stop);
+                }
+                (format!("{}/chat/completions", self.config.base_url.trim_end_matches('/')), request_body)
+            },
+            WireApi::Responses => (
+                format!("{}/responses", self.config.base_url.trim_end_matches('/')),
+                openai_responses::request_body(&self.config, messages),
+            ),
+        };
+
+        info!("Sending POST request to URL: {}", url);
+        info!("Request body: {}", request_body);
+
+        Ok(client.post(&url)
+            .header("Content-Type", "application/json")
+            .json(&request_body))
+    }
+
+    fn authenticate(&self, request: RequestBuilder) -> Result<RequestBuilder, ProviderError> {
+        Ok(request.bearer_auth(&self.config.token))
+    }
+
+    fn parse_stream(&self, response: Response) -> EventStream {
+        match self.config.wire_api.unwrap_or_default() {
+            WireApi::ChatCompletions => chat_completions_stream(response),
+            WireApi::Responses => openai_responses::responses_stream(response),
+        }
+    }
+
+    fn map_error(&self, status: StatusCode, body: &str) -> ProviderError {
+        process_response::map_openai_error(status, body)
+    }
+}
+
+/// Decodes an OpenAI style `chat/completions` stream. Shared by every
+/// backend that speaks the same wire format.
+pub(super) fn chat_completions_stream(response: Response) -> EventStream {
+    let stream = sse_events(response)
+        .take_while(|event| futures::future::ready(!matches!(event, Ok(event) if event.data == "[DONE]")))
+        .flat_map(|event| {
+            futures::stream::iter(match event {
+                Ok(event) => match serde_json::from_str::<Value>(&event.data) {
+                    Ok(chunk) => chat_completions_events(&chunk).into_iter().map(Ok).collect::<Vec<_>>(),
+                    Err(e) => vec![Err(ProviderError::new(format!("Malformed stream chunk: {}", e)))],
+                },
+                Err(e) => vec![Err(e)],
+            })
+        });
+
+    Box::pin(stream)
+}
+
+/// Extracts the events carried by one parsed `chat.completion.chunk`.
+pub(super) fn chat_completions_events(chunk: &Value) -> Vec<StreamEvent> {
+    let mut events = Vec::new();
+    let choice = &chunk["choices"][0];
+    // DeepSeek-R1, QwQ and friends stream their chain of thought separately
+    if let Some(reasoning) = choice["delta"]["reasoning_content"].as_str() {
+        if !reasoning.is_empty() {
+            events.push(StreamEvent::Reasoning(reasoning.to_string()));
+        }
+    }
+    if let Some(content) = choice["delta"]["content"].as_str() {
+        if !content.is_empty() {
+            events.push(StreamEvent::Text(content.to_string()));
+        }
+    }
+    if let Some(reason) = choice["finish_reason"].as_str() {
+        events.push(StreamEvent::Done(Some(reason.to_string())));
+    }
+    // With `include_usage`, the last chunk has the counts and no choices
+    if let Some(usage) = Usage::from_json(&chunk["usage"], "prompt_tokens", "completion_tokens") {
+        events.push(StreamEvent::Usage(usage));
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{render_stream, RenderOptions};
+    use crate::api_client::ApiClient;
+    use crate::config::{AdapterConfig, OpenAIConfig};
+    use crate::providers::mock_server;
+
+    #[tokio::test]
+    async fn test_events_and_done_in_one_chunk() {
+        // The whole stream, [DONE] included, usually arrives in one read
+        let body = concat!(
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Hel\"}}]}\n\n",
+            ": keep-alive\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"lo {\\\"}\\n{\\\"\"}}]}\n\n",
+            "data: [DONE]\n\n",
+        );
+        let (base_url, _server) = mock_server::serve_once(200, "text/event-stream", body).await;
+        let client = ApiClient::new(AdapterConfig::OpenAI(OpenAIConfig {
+            base_url,
+            default_model: "gpt-4o".to_string(),
+            token: "sk-test".to_string(),
+            temperature: 0.7,
+            top_p: None,
+            max_tokens: 64,
+            ..Default::default()
+        }));
+
+        let stream = client.stream_chat(&[ChatMessage::user("hi")]).await.unwrap();
+        assert_eq!(render_stream(stream, &RenderOptions::default()).await.text, "Hello {\"}\n{\"");
+    }
+
+    #[test]
+    fn test_base_url_with_trailing_slash() {
+        let mut config = OpenAIConfig { base_url: "https://api.deepseek.com/".to_string(), ..Default::default() };
+        let request = OpenAIProvider::new(config.clone()).build_request(&Client::new(), &[]).unwrap().build().unwrap();
+        assert_eq!(request.url().as_str(), "https://api.deepseek.com/chat/completions");
+
+        config.wire_api = Some(WireApi::Responses);
+        let request = OpenAIProvider::new(config).build_request(&Client::new(), &[]).unwrap().build().unwrap();
+        assert_eq!(request.url().as_str(), "https://api.deepseek.com/responses");
+    }
+
+    #[test]
+    fn test_reasoning_content() {
+        let chunk = serde_json::json!({
+            "choices": [{ "delta": { "role": "assistant", "content": null, "reasoning_content": "Let me think" } }]
+        });
+        assert_eq!(chat_completions_events(&chunk), vec![StreamEvent::Reasoning("Let me think".to_string())]);
+
+        let chunk = serde_json::json!({
+            "choices": [{ "delta": { "content": "42", "reasoning_content": null }, "finish_reason": "stop" }]
+        });
+        assert_eq!(chat_completions_events(&chunk), vec![
+            StreamEvent::Text("42".to_string()),
+            StreamEvent::Done(Some("stop".to_string())),
+        ]);
+    }
+
+    #[test]
+    fn test_usage_chunk() {
+        let chunk = serde_json::json!({
+            "choices": [],
+            "usage": { "prompt_tokens": 9, "completion_tokens": 12, "total_tokens": 21 }
+        });
+        assert_eq!(chat_completions_events(&chunk), vec![StreamEvent::Usage(Usage { input_tokens: 9, output_tokens: 12 })]);
+    }
+}
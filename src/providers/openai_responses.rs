@@ -0,0 +1,132 @@
+//! The OpenAI Responses API (`/v1/responses`), selected with
+//! `wire_api = "responses"`. It produces the same events as chat/completions,
+//! so the rest of the CLI does not know which one is in use.
+
+use futures::StreamExt;
+use serde_json::Value;
+use crate::config::OpenAIConfig;
+use crate::process_response;
+use super::{sse_events, ChatMessage, EventStream, ProviderError, Role, StreamEvent, Usage};
+
+pub(super) fn request_body(config: &OpenAIConfig, messages: &[ChatMessage]) -> Value {
+    // System prompts become `instructions`; the rest is the input list
+    let instructions = messages.iter()
+        .filter(|message| message.role == Role::System)
+        .map(|message| message.content.as_str())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let input = messages.iter()
+        .filter(|message| message.role != Role::System)
+        .collect::<Vec<_>>();
+
+    let mut request_body = serde_json::json!({
+        "model": config.default_model,
+        "input": input,
+        "temperature": config.temperature,
+        "top_p": config.top_p.unwrap_or(1.0),
+        "max_output_tokens": config.max_tokens,
+        "stream": true,
+    });
+    if !instructions.is_empty() {
+        request_body["instructions"] = Value::String(instructions);
+    }
+    if let Some(summary) = &config.reasoning_summary {
+        request_body["reasoning"] = serde_json::json!({ "summary": summary });
+    }
+    request_body
+}
+
+pub(super) fn responses_stream(response: reqwest::Response) -> EventStream {
+    let stream = sse_events(response).flat_map(|event| futures::stream::iter(match event {
+        Ok(event) => match serde_json::from_str::<Value>(&event.data) {
+            Ok(data) => match responses_events(&data) {
+                Ok(events) => events.into_iter().map(Ok).collect::<Vec<_>>(),
+                Err(e) => vec![Err(e)],
+            },
+            Err(e) => vec![Err(ProviderError::new(format!("OpenAI: malformed Responses event: {}", e)))],
+        },
+        Err(e) => vec![Err(e)],
+    }));
+    Box::pin(stream)
+}
+
+/// Maps one typed Responses API event to stream events.
+fn responses_events(data: &Value) -> Result<Vec<StreamEvent>, ProviderError> {
+    let delta = || data["delta"].as_str().filter(|delta| !delta.is_empty()).map(str::to_string);
+    let finished = |reason: &str| {
+        let usage = Usage::from_json(&data["response"]["usage"], "input_tokens", "output_tokens");
+        usage.map(StreamEvent::Usage).into_iter()
+            .chain([StreamEvent::Done(Some(reason.to_string()))])
+            .collect()
+    };
+    match data["type"].as_str().unwrap_or_default() {
+        "response.output_text.delta" => Ok(delta().map(StreamEvent::Text).into_iter().collect()),
+        "response.reasoning_summary_text.delta" | "response.reasoning_text.delta" => {
+            Ok(delta().map(StreamEvent::Reasoning).into_iter().collect())
+        },
+        "response.completed" => Ok(finished("completed")),
+        "response.incomplete" => Ok(finished(
+            data["response"]["incomplete_details"]["reason"].as_str().unwrap_or("incomplete"),
+        )),
+        "response.failed" => Err(process_response::map_openai_stream_error(&data["response"]["error"])),
+        "error" => Err(process_response::map_openai_stream_error(data)),
+        // Lifecycle and output item bookkeeping carry nothing to print
+        _ => Ok(Vec::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_responses_events() {
+        let delta = serde_json::json!({ "type": "response.output_text.delta", "item_id": "msg_1", "delta": "Hi" });
+        assert_eq!(responses_events(&delta).unwrap(), vec![StreamEvent::Text("Hi".to_string())]);
+
+        let summary = serde_json::json!({ "type": "response.reasoning_summary_text.delta", "delta": "Thinking" });
+        assert_eq!(responses_events(&summary).unwrap(), vec![StreamEvent::Reasoning("Thinking".to_string())]);
+
+        let created = serde_json::json!({ "type": "response.created", "response": { "id": "resp_1" } });
+        assert_eq!(responses_events(&created).unwrap(), vec![]);
+
+        let incomplete = serde_json::json!({
+            "type": "response.incomplete",
+            "response": { "incomplete_details": { "reason": "max_output_tokens" } }
+        });
+        assert_eq!(responses_events(&incomplete).unwrap(), vec![StreamEvent::Done(Some("max_output_tokens".to_string()))]);
+
+        let completed = serde_json::json!({
+            "type": "response.completed",
+            "response": { "usage": { "input_tokens": 12, "output_tokens": 30, "total_tokens": 42 } }
+        });
+        assert_eq!(responses_events(&completed).unwrap(), vec![
+            StreamEvent::Usage(Usage { input_tokens: 12, output_tokens: 30 }),
+            StreamEvent::Done(Some("completed".to_string())),
+        ]);
+
+        let failed = serde_json::json!({
+            "type": "response.failed",
+            "response": { "error": { "code": "server_error", "message": "Something went wrong" } }
+        });
+        assert_eq!(responses_events(&failed).unwrap_err().message, "OpenAI: server_error: Something went wrong");
+    }
+
+    #[test]
+    fn test_request_body() {
+        let config: OpenAIConfig = toml::from_str(r#"
+            base_url = "https://api.openai.com/v1"
+            default_model = "o4-mini"
+            token = "sk-test"
+            temperature = 1.0
+            max_tokens = 256
+            wire_api = "responses"
+            reasoning_summary = "auto"
+        "#).unwrap();
+        let body = request_body(&config, &[ChatMessage::system("Be brief."), ChatMessage::user("hi")]);
+        assert_eq!(body["instructions"], "Be brief.");
+        assert_eq!(body["input"], serde_json::json!([{ "role": "user", "content": "hi" }]));
+        assert_eq!(body["max_output_tokens"], 256);
+        assert_eq!(body["reasoning"]["summary"], "auto");
+    }
+}
@@ -0,0 +1,192 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use log::info;
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use sha2::Sha256;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use crate::config::ChatGLMConfig;
+use crate::process_response;
+use super::openai::chat_completions_stream;
+use super::{ChatMessage, EventStream, Provider, ProviderError};
+
+/// How long a signed token stays valid.
+const TOKEN_TTL: Duration = Duration::from_secs(30 * 60);
+/// Tokens are re-signed this long before they actually expire.
+const TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+struct CachedToken {
+    token: String,
+    expires_at: SystemTime,
+}
+
+pub struct ZhipuProvider {
+    config: ChatGLMConfig,
+    cached_token: Mutex<Option<CachedToken>>,
+}
+
+impl ZhipuProvider {
+    pub fn new(config: ChatGLMConfig) -> Self {
+        ZhipuProvider { config, cached_token: Mutex::new(None) }
+    }
+
+    /// Returns a signed JWT for the configured `id.secret` key, re-using the
+    /// previous one until it is close to expiry.
+    fn auth_token(&self) -> Result<String, ProviderError> {
+        let now = SystemTime::now();
+        let mut cached = self.cached_token.lock().unwrap();
+        if let Some(token) = cached.as_ref() {
+            if now + TOKEN_REFRESH_MARGIN < token.expires_at {
+                return Ok(token.token.clone());
+            }
+        }
+
+        let expires_at = now + TOKEN_TTL;
+        let token = generate_token(&self.config.token, now, expires_at)?;
+        *cached = Some(CachedToken { token: token.clone(), expires_at });
+        Ok(token)
+    }
+}
+
+impl Provider for ZhipuProvider {
+    fn name(&self) -> &'static str {
+        "Zhipu"
+    }
+
+    fn build_request(&self, client: &Client, messages: &[ChatMessage]) -> Result<RequestBuilder, ProviderError> {
+        let url = format!("{}/chat/completions", self.config.base_url.trim_end_matches('/'));
+        let mut request_body = serde_json::json!({
+            "model": self.config.default_model,
+            "messages": messages,
+            "temperature": self.config.temperature,
+            "max_tokens": self.config.max_tokens,
+            "stream": true
+        });
+        if let Some(stop) = &self.config.stop {
+            request_body["stop"] = serde_json::json!(stop);
+        }
+
+        info!("Sending POST request to URL: {}", url);
+        info!("Request body: {}", request_body);
+
+        Ok(client.post(&url)
+            .header("Content-Type", "application/json")
+            .json(&request_body))
+    }
+
+    fn authenticate(&self, request: RequestBuilder) -> Result<RequestBuilder, ProviderError> {
+        Ok(request.bearer_auth(self.auth_token()?))
+    }
+
+    fn parse_stream(&self, response: Response) -> EventStream {
+        // Zhipu speaks the OpenAI chat/completions stream format
+        chat_completions_stream(response)
+    }
+
+    fn map_error(&self, status: StatusCode, body: &str) -> ProviderError {
+        process_response::map_zhipu_error(status, body)
+    }
+}
+
+/// Signs the HS256 JWT the Zhipu API expects from an `id.secret` API key.
+/// Timestamps are in milliseconds, as the API requires.
+fn generate_token(api_key: &str, issued_at: SystemTime, expires_at: SystemTime) -> Result<String, ProviderError> {
+    let (id, secret) = api_key.split_once('.')
+        .filter(|(id, secret)| !id.is_empty() && !secret.is_empty())
+        .ok_or_else(|| ProviderError::new("Zhipu: token must be an API key of the form `id.secret`"))?;
+
+    let millis = |time: SystemTime| time.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+    let header = serde_json::json!({ "alg": "HS256", "sign_type": "SIGN" });
+    let claims = serde_json::json!({
+        "api_key": id,
+        "exp": millis(expires_at),
+        "timestamp": millis(issued_at),
+    });
+
+    let signing_input = format!(
+        "{}.{}",
+        URL_SAFE_NO_PAD.encode(header.to_string()),
+        URL_SAFE_NO_PAD.encode(claims.to_string())
+    );
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .map_err(|e| ProviderError::new(format!("Zhipu: invalid API secret: {}", e)))?;
+    mac.update(signing_input.as_bytes());
+    let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+
+    Ok(format!("{}.{}", signing_input, signature))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_client::ApiClient;
+    use crate::api::{render_stream, RenderOptions};
+    use crate::config::AdapterConfig;
+    use crate::providers::mock_server;
+    use serde_json::Value;
+
+    fn test_config(base_url: &str) -> ChatGLMConfig {
+        ChatGLMConfig {
+            base_url: base_url.to_string(),
+            default_model: "glm-4".to_string(),
+            token: "my-id.my-secret".to_string(),
+            temperature: 0.7,
+            max_tokens: 64,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_generate_token() {
+        let issued_at = UNIX_EPOCH + Duration::from_millis(1_700_000_000_000);
+        let token = generate_token("my-id.my-secret", issued_at, issued_at + TOKEN_TTL).unwrap();
+        let parts: Vec<&str> = token.split('.').collect();
+        assert_eq!(parts.len(), 3);
+
+        let header: Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(parts[0]).unwrap()).unwrap();
+        assert_eq!(header["alg"], "HS256");
+        assert_eq!(header["sign_type"], "SIGN");
+
+        let claims: Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(parts[1]).unwrap()).unwrap();
+        assert_eq!(claims["api_key"], "my-id");
+        assert_eq!(claims["timestamp"], 1_700_000_000_000u64);
+        assert_eq!(claims["exp"], 1_700_000_000_000u64 + 30 * 60 * 1000);
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"my-secret").unwrap();
+        mac.update(format!("{}.{}", parts[0], parts[1]).as_bytes());
+        mac.verify_slice(&URL_SAFE_NO_PAD.decode(parts[2]).unwrap()).unwrap();
+
+        assert!(generate_token("no-secret", issued_at, issued_at).is_err());
+    }
+
+    #[test]
+    fn test_token_is_cached() {
+        let provider = ZhipuProvider::new(test_config("http://localhost"));
+        let first = provider.auth_token().unwrap();
+        assert_eq!(provider.auth_token().unwrap(), first);
+
+        // A token about to expire is replaced
+        provider.cached_token.lock().unwrap().as_mut().unwrap().expires_at = SystemTime::now();
+        std::thread::sleep(Duration::from_millis(2));
+        assert_ne!(provider.auth_token().unwrap(), first);
+    }
+
+    #[tokio::test]
+    async fn test_request_uses_base_url() {
+        let body = "data: {\"choices\":[{\"delta\":{\"content\":\"你好\"}}]}\n\n";
+        let (base_url, server) = mock_server::serve_once(200, "text/event-stream", body).await;
+        let client = ApiClient::new(AdapterConfig::Zhipu(test_config(&format!("{}/api/paas/v4", base_url))));
+
+        let stream = client.stream_chat(&[ChatMessage::user("hi")]).await.unwrap();
+        assert_eq!(render_stream(stream, &RenderOptions::default()).await.text, "你好");
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /api/paas/v4/chat/completions "));
+        let auth = request.lines()
+            .find_map(|line| line.strip_prefix("authorization: Bearer "))
+            .expect("missing bearer token");
+        assert_eq!(auth.split('.').count(), 3);
+        assert!(!auth.contains("my-secret"));
+    }
+}
@@ -0,0 +1,153 @@
+//! Named roles: a system prompt with optional few-shot examples and
+//! preferred generation settings, selected with `--role`. Built-in roles can
+//! be replaced by a `[roles.<name>]` table of the same name.
+
+use clap::ArgMatches;
+use std::collections::BTreeMap;
+use std::error::Error;
+use crate::config::{self, Config, RoleConfig};
+use crate::overrides::GenerationOverrides;
+use crate::providers::ChatMessage;
+
+pub fn builtin_roles() -> BTreeMap<String, RoleConfig> {
+    let mut roles = BTreeMap::new();
+    roles.insert("shell".to_string(), RoleConfig {
+        description: Some("Replies with a single shell command".to_string()),
+        system_prompt: format!(
+            "You are a shell expert on {}. Reply with only the command that does what the user asks, \
+             without explanation or Markdown code fences. Join several commands with && on one line.",
+            std::env::consts::OS
+        ),
+        examples: vec![
+            ChatMessage::user("find files larger than 100MB in the current directory"),
+            ChatMessage::assistant("find . -type f -size +100M"),
+        ],
+        temperature: Some(0.2),
+        ..Default::default()
+    });
+    roles.insert("code".to_string(), RoleConfig {
+        description: Some("Replies with code only".to_string()),
+        system_prompt: "You are an expert programmer. Reply with only the code that answers the request, \
+            without surrounding prose or Markdown code fences. Put any necessary notes in code comments."
+            .to_string(),
+        temperature: Some(0.2),
+        ..Default::default()
+    });
+    roles.insert("explain".to_string(), RoleConfig {
+        description: Some("Explains a concept, command or piece of code".to_string()),
+        system_prompt: "Explain what the user gives you clearly and concisely, for someone new to the topic. \
+            Start with a one-sentence summary, then the details, with a short example where it helps."
+            .to_string(),
+        ..Default::default()
+    });
+    roles.insert("translate".to_string(), RoleConfig {
+        description: Some("Translates between English and Chinese".to_string()),
+        system_prompt: "You are a translator. If the user's text is in Chinese, translate it into English; \
+            otherwise translate it into Chinese. Reply with only the translation, keeping the formatting."
+            .to_string(),
+        temperature: Some(0.3),
+        ..Default::default()
+    });
+    roles
+}
+
+/// Built-in roles overlaid with the ones from the config, and whether each
+/// comes from the config.
+pub fn all_roles(config: &Config) -> BTreeMap<String, (RoleConfig, bool)> {
+    let mut roles: BTreeMap<String, (RoleConfig, bool)> = builtin_roles().into_iter()
+        .map(|(name, role)| (name, (role, false)))
+        .collect();
+    for (name, role) in &config.roles {
+        roles.insert(name.clone(), (role.clone(), true));
+    }
+    roles
+}
+
+pub fn get_role(config: &Config, name: &str) -> Result<RoleConfig, Box<dyn Error>> {
+    let mut roles = all_roles(config);
+    match roles.remove(name) {
+        Some((role, _)) => Ok(role),
+        None => {
+            let valid_names = roles.keys().cloned().collect::<Vec<_>>();
+            Err(format!("Unknown role '{}'. Valid roles: {}", name, valid_names.join(", ")).into())
+        }
+    }
+}
+
+impl RoleConfig {
+    /// The role's settings as overrides, applied before the command line's.
+    pub fn overrides(&self) -> GenerationOverrides {
+        GenerationOverrides {
+            model: self.model.clone(),
+            temperature: self.temperature,
+            system: Some(self.system_prompt.clone()),
+            ..Default::default()
+        }
+    }
+}
+
+/// Runs `ruskgpt roles <subcommand>`. Returns the process exit code.
+pub fn handle_roles_command(matches: &ArgMatches, config: &Config) -> i32 {
+    match matches.subcommand() {
+        Some(("list", _)) => {
+            for (name, (role, from_config)) in all_roles(config) {
+                let source = match (from_config, builtin_roles().contains_key(&name)) {
+                    (false, _) => "built-in",
+                    (true, true) => "config, overrides built-in",
+                    (true, false) => "config",
+                };
+                println!("{:<12} {} ({})", name, role.description.as_deref().unwrap_or(""), source);
+            }
+            0
+        },
+        Some(("show", args)) => {
+            let name = args.get_one::<String>("name").unwrap();
+            match get_role(config, name).and_then(|role| config::to_toml_string(&role)) {
+                Ok(role) => {
+                    print!("{}", role);
+                    0
+                },
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    1
+                },
+            }
+        },
+        _ => unreachable!("clap requires a roles subcommand"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AdapterConfig;
+
+    #[test]
+    fn test_config_role_overrides_builtin() {
+        let mut config = Config::from_file("config.toml.example").unwrap();
+        config.roles.insert("shell".to_string(), RoleConfig {
+            system_prompt: "Reply with a PowerShell command.".to_string(),
+            ..Default::default()
+        });
+
+        let shell = get_role(&config, "shell").unwrap();
+        assert_eq!(shell.system_prompt, "Reply with a PowerShell command.");
+        assert!(shell.examples.is_empty());
+        assert!(get_role(&config, "translate").unwrap().examples.is_empty());
+        assert_eq!(
+            get_role(&config, "poet").unwrap_err().to_string(),
+            "Unknown role 'poet'. Valid roles: code, commit, explain, shell, translate"
+        );
+    }
+
+    #[test]
+    fn test_role_overrides_apply_to_adapter() {
+        let config = Config::from_file("config.toml.example").unwrap();
+        let mut adapter = config.adapters["claude_adapter"].clone();
+        get_role(&config, "shell").unwrap().overrides().apply(&mut adapter).unwrap();
+
+        let AdapterConfig::Claude(claude) = adapter else { unreachable!() };
+        assert_eq!(claude.temperature, 0.2);
+        assert!(claude.system_prompt.unwrap().starts_with("You are a shell expert"));
+    }
+}
@@ -0,0 +1,149 @@
+//! Resolves API tokens from the config without keeping them in plaintext:
+//! `${VAR}` interpolation, a `token_cmd` whose output is the token, or a
+//! `token_file`. Only the resolved copy handed to the client holds the secret.
+
+use log::warn;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command as ProcessCommand;
+use std::sync::{Mutex, OnceLock};
+
+/// `token_cmd` output, so each command runs at most once per process.
+static COMMAND_CACHE: OnceLock<Mutex<HashMap<String, String>>> = OnceLock::new();
+
+pub fn resolve_token(token: &str, token_cmd: Option<&str>, token_file: Option<&str>) -> Result<String, Box<dyn Error>> {
+    let sources = [!token.is_empty(), token_cmd.is_some(), token_file.is_some()];
+    if sources.iter().filter(|&&set| set).count() > 1 {
+        return Err("Set only one of token, token_cmd and token_file".into());
+    }
+
+    if let Some(command) = token_cmd {
+        return run_token_command(command);
+    }
+    if let Some(path) = token_file {
+        return read_token_file(path);
+    }
+    interpolate_env(token)
+}
+
+/// Replaces every `${VAR}` in `value` with the environment variable's value.
+pub fn interpolate_env(value: &str) -> Result<String, Box<dyn Error>> {
+    let mut result = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        result.push_str(&rest[..start]);
+        let end = rest[start..].find('}')
+            .ok_or_else(|| format!("Unterminated ${{...}} in \"{}\"", value))?;
+        let name = &rest[start + 2..start + end];
+        let resolved = std::env::var(name)
+            .map_err(|_| format!("Environment variable {} is not set", name))?;
+        result.push_str(&resolved);
+        rest = &rest[start + end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+fn run_token_command(command: &str) -> Result<String, Box<dyn Error>> {
+    let cache = COMMAND_CACHE.get_or_init(|| Mutex::new(HashMap::new()));
+    if let Some(token) = cache.lock().unwrap().get(command) {
+        return Ok(token.clone());
+    }
+
+    let output = if cfg!(windows) {
+        ProcessCommand::new("cmd").args(["/C", command]).output()
+    } else {
+        ProcessCommand::new("sh").args(["-c", command]).output()
+    }.map_err(|e| format!("Failed to run token_cmd `{}`: {}", command, e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "token_cmd `{}` failed ({}): {}",
+            command,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ).into());
+    }
+
+    // Tools like `pass` print the secret on the first line
+    let stdout = String::from_utf8(output.stdout)?;
+    let token = stdout.lines().next().unwrap_or_default().trim().to_string();
+    if token.is_empty() {
+        return Err(format!("token_cmd `{}` printed no token", command).into());
+    }
+
+    cache.lock().unwrap().insert(command.to_string(), token.clone());
+    Ok(token)
+}
+
+fn read_token_file(path: &str) -> Result<String, Box<dyn Error>> {
+    let path = expand_home(path);
+    warn_if_readable_by_others(&path);
+
+    let content = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read token_file {}: {}", path.display(), e))?;
+    let token = content.trim().to_string();
+    if token.is_empty() {
+        return Err(format!("token_file {} is empty", path.display()).into());
+    }
+    Ok(token)
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix("~/") {
+        Some(rest) => dirs_next::home_dir().map(|home| home.join(rest)).unwrap_or_else(|| PathBuf::from(path)),
+        None => PathBuf::from(path),
+    }
+}
+
+#[cfg(unix)]
+fn warn_if_readable_by_others(path: &std::path::Path) {
+    use std::os::unix::fs::PermissionsExt;
+    if let Ok(metadata) = fs::metadata(path) {
+        let mode = metadata.permissions().mode();
+        if mode & 0o077 != 0 {
+            warn!("token_file {} has mode {:o}", path.display(), mode & 0o777);
+            eprintln!(
+                "Warning: token_file {} is accessible by other users (mode {:o}); run `chmod 600 {}`",
+                path.display(),
+                mode & 0o777,
+                path.display()
+            );
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn warn_if_readable_by_others(_path: &std::path::Path) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interpolate_env() {
+        std::env::set_var("RUSKGPT_TEST_KEY", "sk-from-env");
+        assert_eq!(interpolate_env("${RUSKGPT_TEST_KEY}").unwrap(), "sk-from-env");
+        assert_eq!(interpolate_env("prefix-${RUSKGPT_TEST_KEY}-suffix").unwrap(), "prefix-sk-from-env-suffix");
+        assert_eq!(interpolate_env("plain-token").unwrap(), "plain-token");
+        assert_eq!(
+            interpolate_env("${RUSKGPT_TEST_MISSING}").unwrap_err().to_string(),
+            "Environment variable RUSKGPT_TEST_MISSING is not set"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_token_cmd_and_file() {
+        assert_eq!(resolve_token("", Some("echo sk-from-cmd"), None).unwrap(), "sk-from-cmd");
+        assert!(resolve_token("", Some("exit 3"), None).is_err());
+        assert!(resolve_token("sk-plain", Some("echo sk-from-cmd"), None).is_err());
+
+        let path = std::env::temp_dir().join(format!("ruskgpt-token-{}", std::process::id()));
+        fs::write(&path, "sk-from-file\n").unwrap();
+        assert_eq!(resolve_token("", None, path.to_str()).unwrap(), "sk-from-file");
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,225 @@
+//! Renders a session as Markdown, HTML or JSON for `sessions export` and the
+//! chat's `/save`. Reasoning and tool calls get their own sections, apart
+//! from the answer they led to.
+
+use chrono::Local;
+use serde::Serialize;
+use std::error::Error;
+use std::path::Path;
+use crate::providers::Role;
+use crate::sessions::{Session, SessionMessage, ToolCall};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Markdown,
+    Html,
+    Json,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Result<Self, Box<dyn Error>> {
+        match name {
+            "md" | "markdown" => Ok(Format::Markdown),
+            "html" => Ok(Format::Html),
+            "json" => Ok(Format::Json),
+            other => Err(format!("Unknown export format '{}'. Valid formats: md, html, json", other).into()),
+        }
+    }
+
+    /// The format a file name asks for; Markdown unless it ends in `.html`
+    /// or `.json`.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("html" | "htm") => Format::Html,
+            Some("json") => Format::Json,
+            _ => Format::Markdown,
+        }
+    }
+}
+
+pub fn export(session: &Session, format: Format) -> Result<String, Box<dyn Error>> {
+    match format {
+        Format::Markdown => Ok(to_markdown(session)),
+        Format::Html => Ok(to_html(session)),
+        Format::Json => to_json(session),
+    }
+}
+
+fn heading(message: &SessionMessage) -> &'static str {
+    match message.message.role {
+        Role::System => "System",
+        Role::User => "You",
+        Role::Assistant => "Assistant",
+    }
+}
+
+fn details(session: &Session) -> Vec<String> {
+    let mut details = vec![
+        format!("Created: {}", session.created_at.with_timezone(&Local).format("%Y-%m-%d %H:%M")),
+        format!("Updated: {}", session.updated_at.with_timezone(&Local).format("%Y-%m-%d %H:%M")),
+    ];
+    if !session.model.is_empty() {
+        details.push(format!("Model: {} ({})", session.model, session.adapter));
+    }
+    if session.usage.input_tokens + session.usage.output_tokens > 0 {
+        details.push(format!("Usage: {} input, {} output tokens", session.usage.input_tokens, session.usage.output_tokens));
+    }
+    details
+}
+
+/// A code fence longer than any run of backticks in `text`.
+pub fn fence(text: &str) -> String {
+    let longest = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
+
+fn markdown_tool_call(text: &mut String, call: &ToolCall) {
+    let input_fence = fence(&call.input);
+    text.push_str(&format!("**Tool call: {}**\n\n{}\n{}\n{}\n\n", call.name, input_fence, call.input.trim_end(), input_fence));
+    if let Some(output) = &call.output {
+        let output_fence = fence(output);
+        text.push_str(&format!("**Output**\n\n{}\n{}\n{}\n\n", output_fence, output.trim_end(), output_fence));
+    }
+}
+
+pub fn to_markdown(session: &Session) -> String {
+    let mut text = format!("# {}\n\n", session.name);
+    for line in details(session) {
+        text.push_str(&format!("- {}\n", line));
+    }
+    text.push('\n');
+
+    for message in &session.messages {
+        text.push_str(&format!("## {}\n\n", heading(message)));
+        if let Some(reasoning) = &message.reasoning {
+            text.push_str("> **Reasoning**\n>\n");
+            for line in reasoning.trim_end().lines() {
+                if line.is_empty() {
+                    text.push_str(">\n");
+                } else {
+                    text.push_str(&format!("> {}\n", line));
+                }
+            }
+            text.push('\n');
+        }
+        for call in &message.tool_calls {
+            markdown_tool_call(&mut text, call);
+        }
+        if !message.message.content.trim().is_empty() {
+            text.push_str(&format!("{}\n\n", message.message.content.trim_end()));
+        }
+    }
+    text
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+const HTML_STYLE: &str = "\
+body { font-family: system-ui, sans-serif; max-width: 50rem; margin: 2rem auto; padding: 0 1rem; color: #222; }
+.meta { color: #666; }
+.message { border-radius: 6px; padding: 0.5rem 1rem; margin: 1rem 0; }
+.user { background: #eef4ff; }
+.assistant { background: #f6f6f6; }
+.system { background: #fff8e6; }
+.content { white-space: pre-wrap; font-family: inherit; }
+details { margin: 0.5rem 0; }
+summary { cursor: pointer; color: #555; }
+pre.code { background: #272822; color: #f8f8f2; padding: 0.5rem; overflow-x: auto; }";
+
+pub fn to_html(session: &Session) -> String {
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{}\n</style>\n</head>\n<body>\n<h1>{}</h1>\n",
+        escape_html(&session.name), HTML_STYLE, escape_html(&session.name)
+    );
+    html.push_str("<p class=\"meta\">");
+    html.push_str(&details(session).iter().map(|line| escape_html(line)).collect::<Vec<_>>().join("<br>\n"));
+    html.push_str("</p>\n");
+
+    for message in &session.messages {
+        let class = heading(message).to_lowercase().replace("you", "user");
+        html.push_str(&format!("<div class=\"message {}\">\n<h2>{}</h2>\n", class, heading(message)));
+        if let Some(reasoning) = &message.reasoning {
+            html.push_str(&format!(
+                "<details class=\"reasoning\">\n<summary>Reasoning</summary>\n<pre class=\"content\">{}</pre>\n</details>\n",
+                escape_html(reasoning.trim_end())
+            ));
+        }
+        for call in &message.tool_calls {
+            html.push_str(&format!(
+                "<details class=\"tool-call\" open>\n<summary>Tool call: {}</summary>\n<pre class=\"code\">{}</pre>\n",
+                escape_html(&call.name), escape_html(call.input.trim_end())
+            ));
+            if let Some(output) = &call.output {
+                html.push_str(&format!("<p>Output</p>\n<pre class=\"code\">{}</pre>\n", escape_html(output.trim_end())));
+            }
+            html.push_str("</details>\n");
+        }
+        if !message.message.content.trim().is_empty() {
+            html.push_str(&format!("<pre class=\"content\">{}</pre>\n", escape_html(message.message.content.trim_end())));
+        }
+        html.push_str("</div>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// The session file's contents, with its name added.
+pub fn to_json(session: &Session) -> Result<String, Box<dyn Error>> {
+    #[derive(Serialize)]
+    struct NamedSession<'a> {
+        name: &'a str,
+        #[serde(flatten)]
+        session: &'a Session,
+    }
+    Ok(serde_json::to_string_pretty(&NamedSession { name: &session.name, session })? + "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::ChatMessage;
+
+    fn sample_session() -> Session {
+        let mut session = Session::new("plots");
+        session.push(SessionMessage::question("Plot y = x^2 <quickly>"));
+        let mut answer = SessionMessage::new(ChatMessage::assistant("Here is the plot."), chrono::Utc::now());
+        answer.reasoning = Some("The user wants a parabola.\n\nUse matplotlib.".to_string());
+        answer.tool_calls.push(ToolCall {
+            name: "python".to_string(),
+            input: "plt.plot(x, x ** 2)\nprint(\"```\")".to_string(),
+            output: Some("```".to_string()),
+        });
+        session.push(answer);
+        session
+    }
+
+    #[test]
+    fn test_markdown_sections() {
+        let markdown = to_markdown(&sample_session());
+        assert!(markdown.starts_with("# plots\n\n- Created: "));
+        assert!(markdown.contains("## Assistant\n\n> **Reasoning**\n>\n> The user wants a parabola.\n>\n> Use matplotlib.\n\n"));
+        // The fence outgrows the backticks inside the code
+        assert!(markdown.contains("**Tool call: python**\n\n````\nplt.plot(x, x ** 2)\nprint(\"```\")\n````\n\n**Output**\n\n````\n```\n````\n\nHere is the plot.\n"));
+    }
+
+    #[test]
+    fn test_html_is_escaped() {
+        let html = to_html(&sample_session());
+        assert!(html.contains("<pre class=\"content\">Plot y = x^2 &lt;quickly&gt;</pre>"));
+        assert!(html.contains("<summary>Reasoning</summary>"));
+        assert!(html.contains("<summary>Tool call: python</summary>\n<pre class=\"code\">plt.plot(x, x ** 2)\nprint(&quot;```&quot;)</pre>"));
+        assert_eq!(html.matches("<div class=\"message user\">").count(), 1);
+    }
+
+    #[test]
+    fn test_json_round_trips() {
+        let session = sample_session();
+        let json = to_json(&session).unwrap();
+        let mut parsed: Session = serde_json::from_str(&json).unwrap();
+        parsed.name = session.name.clone();
+        assert_eq!(parsed, session);
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&json).unwrap()["name"], "plots");
+    }
+}
@@ -0,0 +1,298 @@
+//! Imports conversations from a ChatGPT data export: the zip file from
+//! Settings > Data controls > Export data, or the `conversations.json` inside
+//! it. Each conversation becomes a session.
+
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+use crate::providers::ChatMessage;
+use crate::sessions::{Session, SessionMessage, SessionStore, ToolCall};
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ImportSummary {
+    pub imported: Vec<String>,
+    /// Sessions that were imported before and left alone.
+    pub existing: Vec<String>,
+    /// Conversations without a visible message.
+    pub empty: usize,
+}
+
+pub fn import_chatgpt(path: &Path, store: &SessionStore) -> Result<ImportSummary, Box<dyn Error>> {
+    let json = read_conversations(path)?;
+    let conversations: Vec<Value> = serde_json::from_str(&json)
+        .map_err(|e| format!("{}: not a ChatGPT conversations.json: {}", path.display(), e))?;
+
+    let mut summary = ImportSummary::default();
+    for conversation in &conversations {
+        let session = conversation_to_session(conversation);
+        if session.messages.is_empty() {
+            summary.empty += 1;
+        } else if store.create(&session)? {
+            summary.imported.push(session.name);
+        } else {
+            summary.existing.push(session.name);
+        }
+    }
+    Ok(summary)
+}
+
+/// The `conversations.json` text, from the export zip or the file itself.
+fn read_conversations(path: &Path) -> Result<String, Box<dyn Error>> {
+    let mut file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut magic = [0u8; 2];
+    let is_zip = file.read(&mut magic)? == 2 && magic == *b"PK";
+    if !is_zip {
+        return Ok(fs::read_to_string(path)?);
+    }
+
+    let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+    let name = archive.file_names()
+        .find(|name| name.rsplit('/').next() == Some("conversations.json"))
+        .map(str::to_string)
+        .ok_or_else(|| format!("{}: no conversations.json in the archive", path.display()))?;
+    let mut json = String::new();
+    archive.by_name(&name)?.read_to_string(&mut json)?;
+    Ok(json)
+}
+
+fn timestamp(value: &Value) -> Option<DateTime<Utc>> {
+    let seconds = value.as_f64()?;
+    DateTime::from_timestamp(seconds.trunc() as i64, (seconds.fract() * 1e9) as u32)
+}
+
+/// A file-safe name from the title, plus the start of the conversation id so
+/// importing the same export twice finds the same sessions.
+fn session_name(title: &str, id: &str) -> String {
+    let mut slug = String::new();
+    for c in title.to_lowercase().chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.chars().take(40).collect::<String>().trim_end_matches('-').to_string();
+    let slug = if slug.is_empty() { "chatgpt".to_string() } else { slug };
+    let id = id.chars().filter(char::is_ascii_alphanumeric).take(8).collect::<String>();
+    if id.is_empty() { slug } else { format!("{}-{}", slug, id) }
+}
+
+/// ChatGPT stores a tree of messages, one branch per edit or regeneration.
+/// This follows the branch that was on screen: from `current_node` up to
+/// the root, or down the latest children when there is no current node.
+fn visible_branch(conversation: &Value) -> Vec<&Value> {
+    let mapping = &conversation["mapping"];
+    let mut branch = Vec::new();
+    if let Some(mut id) = conversation["current_node"].as_str() {
+        while let Some(node) = mapping.get(id) {
+            branch.push(node);
+            match node["parent"].as_str() {
+                Some(parent) => id = parent,
+                None => break,
+            }
+        }
+        branch.reverse();
+    } else if let Some(nodes) = mapping.as_object() {
+        let mut node = nodes.values().find(|node| node["parent"].is_null());
+        while let Some(current) = node {
+            branch.push(current);
+            node = current["children"].as_array()
+                .and_then(|children| children.last())
+                .and_then(|child| child.as_str())
+                .and_then(|child| mapping.get(child));
+        }
+    }
+    branch
+}
+
+/// The readable text of a message's content.
+fn content_text(content: &Value) -> String {
+    if let Some(parts) = content["parts"].as_array() {
+        return parts.iter()
+            .map(|part| match part {
+                Value::String(text) => text.clone(),
+                // Uploaded images and files are referenced, not included
+                _ => format!("[{}]", part["content_type"].as_str().unwrap_or("attachment")),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+    content["text"].as_str().unwrap_or_default().to_string()
+}
+
+fn conversation_to_session(conversation: &Value) -> Session {
+    let title = conversation["title"].as_str().unwrap_or("Untitled");
+    let id = conversation["conversation_id"].as_str().or(conversation["id"].as_str()).unwrap_or_default();
+    let created_at = timestamp(&conversation["create_time"]).unwrap_or_else(Utc::now);
+
+    let mut session = Session::new(session_name(title, id));
+    session.created_at = created_at;
+    session.updated_at = created_at;
+
+    // Reasoning and tool calls are attached to the answer that follows them
+    let mut reasoning: Vec<String> = Vec::new();
+    let mut tool_calls: Vec<ToolCall> = Vec::new();
+    for node in visible_branch(conversation) {
+        let message = &node["message"];
+        if message.is_null() || message["metadata"]["is_visually_hidden_from_conversation"].as_bool() == Some(true) {
+            continue;
+        }
+        let content = &message["content"];
+        let text = content_text(content);
+        let time = timestamp(&message["create_time"]).unwrap_or(session.updated_at);
+
+        match (message["author"]["role"].as_str(), content["content_type"].as_str()) {
+            // Custom instructions arrive as a hidden user message
+            (Some("user"), Some(content_type)) if content_type != "user_editable_context" && !text.trim().is_empty() => {
+                flush_pending(&mut session, &mut reasoning, &mut tool_calls, time);
+                session.push(SessionMessage::new(ChatMessage::user(text), time));
+            },
+            (Some("assistant"), Some("thoughts")) => {
+                for thought in content["thoughts"].as_array().into_iter().flatten() {
+                    let summary = thought["summary"].as_str().unwrap_or_default();
+                    let body = thought["content"].as_str().unwrap_or_default();
+                    reasoning.push(format!("{}\n{}", summary, body).trim().to_string());
+                }
+            },
+            // "Thought for 12 seconds"
+            (Some("assistant"), Some("reasoning_recap")) => {},
+            (Some("assistant"), _) if message["recipient"].as_str().unwrap_or("all") != "all" => {
+                tool_calls.push(ToolCall {
+                    name: message["recipient"].as_str().unwrap_or_default().to_string(),
+                    input: text,
+                    output: None,
+                });
+            },
+            (Some("assistant"), _) if !text.trim().is_empty() => {
+                let mut answer = SessionMessage::new(ChatMessage::assistant(text), time);
+                answer.adapter = Some("chatgpt".to_string());
+                answer.model = message["metadata"]["model_slug"].as_str().map(str::to_string);
+                answer.reasoning = Some(reasoning.join("\n\n")).filter(|reasoning| !reasoning.is_empty());
+                answer.tool_calls = std::mem::take(&mut tool_calls);
+                reasoning.clear();
+                session.push(answer);
+            },
+            (Some("tool"), _) => {
+                let name = message["author"]["name"].as_str().unwrap_or("tool");
+                match tool_calls.iter_mut().rev().find(|call| call.output.is_none() && name.starts_with(&call.name)) {
+                    Some(call) => call.output = Some(text),
+                    None => tool_calls.push(ToolCall { name: name.to_string(), input: String::new(), output: Some(text) }),
+                }
+            },
+            // System messages are ChatGPT's own and hidden from the user, and
+            // empty messages have nothing to show
+            _ => {},
+        }
+    }
+    let end = timestamp(&conversation["update_time"]).unwrap_or(session.updated_at);
+    flush_pending(&mut session, &mut reasoning, &mut tool_calls, end);
+    session.updated_at = session.updated_at.max(end);
+    session
+}
+
+/// Tool calls or reasoning that no answer followed get an answer of their
+/// own, so they are not lost.
+fn flush_pending(session: &mut Session, reasoning: &mut Vec<String>, tool_calls: &mut Vec<ToolCall>, time: DateTime<Utc>) {
+    if reasoning.is_empty() && tool_calls.is_empty() {
+        return;
+    }
+    let mut answer = SessionMessage::new(ChatMessage::assistant(""), time);
+    answer.adapter = Some("chatgpt".to_string());
+    answer.reasoning = Some(reasoning.join("\n\n")).filter(|reasoning| !reasoning.is_empty());
+    answer.tool_calls = std::mem::take(tool_calls);
+    reasoning.clear();
+    session.push(answer);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// One conversation with an edited question (two branches), a code
+    /// interpreter call and a reasoning model's thoughts.
+    fn sample_export() -> Value {
+        serde_json::json!([{
+            "title": "Plot a parabola!",
+            "create_time": 1700000000.5,
+            "update_time": 1700000100.0,
+            "conversation_id": "6721a3bc-0000-4000-8000-000000000000",
+            "current_node": "answer",
+            "mapping": {
+                "root": { "id": "root", "message": null, "parent": null, "children": ["system"] },
+                "system": { "id": "system", "parent": "root", "children": ["old-question", "question"], "message": {
+                    "author": { "role": "system" }, "create_time": null,
+                    "content": { "content_type": "text", "parts": [""] },
+                    "metadata": { "is_visually_hidden_from_conversation": true }, "recipient": "all"
+                } },
+                "old-question": { "id": "old-question", "parent": "system", "children": [], "message": {
+                    "author": { "role": "user" }, "create_time": 1700000001.0,
+                    "content": { "content_type": "text", "parts": ["plot x^3"] }, "recipient": "all"
+                } },
+                "question": { "id": "question", "parent": "system", "children": ["thoughts"], "message": {
+                    "author": { "role": "user" }, "create_time": 1700000002.0,
+                    "content": { "content_type": "text", "parts": ["plot x^2"] }, "recipient": "all"
+                } },
+                "thoughts": { "id": "thoughts", "parent": "question", "children": ["code"], "message": {
+                    "author": { "role": "assistant" }, "create_time": 1700000003.0,
+                    "content": { "content_type": "thoughts", "thoughts": [{ "summary": "Choosing a library", "content": "matplotlib is fine." }] },
+                    "recipient": "all"
+                } },
+                "code": { "id": "code", "parent": "thoughts", "children": ["output"], "message": {
+                    "author": { "role": "assistant" }, "create_time": 1700000004.0,
+                    "content": { "content_type": "code", "language": "unknown", "text": "plt.plot(x, x**2)" },
+                    "recipient": "python"
+                } },
+                "output": { "id": "output", "parent": "code", "children": ["answer"], "message": {
+                    "author": { "role": "tool", "name": "python" }, "create_time": 1700000005.0,
+                    "content": { "content_type": "execution_output", "text": "[<matplotlib.lines.Line2D>]" },
+                    "recipient": "all"
+                } },
+                "answer": { "id": "answer", "parent": "output", "children": [], "message": {
+                    "author": { "role": "assistant" }, "create_time": 1700000006.0,
+                    "content": { "content_type": "text", "parts": ["Here is the parabola."] },
+                    "metadata": { "model_slug": "o3" }, "recipient": "all"
+                } }
+            }
+        }])
+    }
+
+    #[test]
+    fn test_conversation_to_session() {
+        let session = conversation_to_session(&sample_export()[0]);
+        assert_eq!(session.name, "plot-a-parabola-6721a3bc");
+        assert_eq!(session.chat_messages(), vec![ChatMessage::user("plot x^2"), ChatMessage::assistant("Here is the parabola.")]);
+
+        let answer = &session.messages[1];
+        assert_eq!(answer.model.as_deref(), Some("o3"));
+        assert_eq!(answer.reasoning.as_deref(), Some("Choosing a library\nmatplotlib is fine."));
+        assert_eq!(answer.tool_calls, vec![ToolCall {
+            name: "python".to_string(),
+            input: "plt.plot(x, x**2)".to_string(),
+            output: Some("[<matplotlib.lines.Line2D>]".to_string()),
+        }]);
+        assert_eq!(session.created_at.timestamp(), 1700000000);
+        assert_eq!(session.updated_at.timestamp(), 1700000100);
+    }
+
+    #[test]
+    fn test_import_zip_twice() {
+        let dir = std::env::temp_dir().join(format!("ruskgpt-import-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let zip_path = dir.join("export.zip");
+        let mut writer = zip::ZipWriter::new(File::create(&zip_path).unwrap());
+        writer.start_file("conversations.json", zip::write::SimpleFileOptions::default()).unwrap();
+        writer.write_all(sample_export().to_string().as_bytes()).unwrap();
+        writer.finish().unwrap();
+
+        let store = SessionStore::new(dir.join("sessions"));
+        let summary = import_chatgpt(&zip_path, &store).unwrap();
+        assert_eq!(summary.imported, vec!["plot-a-parabola-6721a3bc".to_string()]);
+        assert_eq!(import_chatgpt(&zip_path, &store).unwrap().existing, summary.imported);
+        assert_eq!(store.load("plot-a-parabola-6721a3bc").unwrap().messages.len(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,554 @@
+//! Conversations kept on disk, one JSON file per session in
+//! `$XDG_DATA_HOME/ruskgpt/sessions`. Every question is recorded, and
+//! `--session` or `--continue` carries an earlier conversation forward.
+
+use chrono::{DateTime, Local, Utc};
+use clap::ArgMatches;
+use fd_lock::RwLock;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use crate::api::Answer;
+use crate::providers::{ChatMessage, Role, Usage};
+use crate::{session_export, session_import};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionMessage {
+    #[serde(flatten)]
+    pub message: ChatMessage,
+    pub timestamp: DateTime<Utc>,
+    /// The adapter and model that wrote an answer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub adapter: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+    /// The model's reasoning before an answer. Kept for reading, never sent
+    /// back to the model.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<String>,
+    /// Tools the model called on the way to an answer.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    /// Set on a summary of the conversation's first messages, made to fit the
+    /// context window: how many of them it stands in for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary_of: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub name: String,
+    pub input: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+}
+
+impl SessionMessage {
+    pub fn new(message: ChatMessage, timestamp: DateTime<Utc>) -> Self {
+        SessionMessage { message, timestamp, adapter: None, model: None, usage: None, reasoning: None, tool_calls: Vec::new(), summary_of: None }
+    }
+
+    pub fn question(content: impl Into<String>) -> Self {
+        Self::new(ChatMessage::user(content), Utc::now())
+    }
+
+    pub fn answer(answer: Answer, adapter: &str, model: &str) -> Self {
+        SessionMessage {
+            adapter: Some(adapter.to_string()),
+            model: Some(model.to_string()),
+            usage: answer.usage,
+            reasoning: Some(answer.reasoning).filter(|reasoning| !reasoning.is_empty()),
+            ..Self::new(ChatMessage::assistant(answer.text), Utc::now())
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    /// The file name without `.json`; not stored in the file itself.
+    #[serde(skip)]
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// The adapter and model of the latest answer.
+    #[serde(default)]
+    pub adapter: String,
+    #[serde(default)]
+    pub model: String,
+    /// Tokens used by every answer together.
+    #[serde(default)]
+    pub usage: Usage,
+    #[serde(default)]
+    pub messages: Vec<SessionMessage>,
+    /// Set on a new session that hasn't been written yet. Its name, from the
+    /// time it was started, is made unique when it is first saved.
+    #[serde(skip)]
+    pub unsaved: bool,
+}
+
+impl Session {
+    pub fn new(name: impl Into<String>) -> Self {
+        let now = Utc::now();
+        Session {
+            name: name.into(),
+            created_at: now,
+            updated_at: now,
+            adapter: String::new(),
+            model: String::new(),
+            usage: Usage::default(),
+            messages: Vec::new(),
+            unsaved: false,
+        }
+    }
+
+    /// The latest summary and how many messages it stands in for.
+    fn latest_summary(&self) -> Option<(&SessionMessage, usize)> {
+        self.messages.iter().rev().find_map(|message| message.summary_of.map(|covered| (message, covered)))
+    }
+
+    /// The conversation's messages from the `covered`-th on, with their
+    /// position among them, leaving out summaries and messages with no text.
+    /// Imported answers may hold only tool calls or reasoning, and providers
+    /// reject empty content.
+    fn sent_after(&self, covered: usize) -> impl Iterator<Item = (usize, &SessionMessage)> {
+        self.messages.iter()
+            .filter(|message| message.summary_of.is_none())
+            .enumerate()
+            .skip(covered)
+            .filter(|(_, message)| !message.message.content.is_empty())
+    }
+
+    /// The conversation as it is sent to the model. Turns that were
+    /// summarized are replaced by the latest summary.
+    pub fn chat_messages(&self) -> Vec<ChatMessage> {
+        let (mut messages, covered) = match self.latest_summary() {
+            Some((summary, covered)) => (vec![summary.message.clone()], covered),
+            None => (Vec::new(), 0),
+        };
+        messages.extend(self.sent_after(covered).map(|(_, message)| message.message.clone()));
+        messages
+    }
+
+    /// A summary to save with the next exchange, given that it replaced the
+    /// first `replaced` messages of `chat_messages()`.
+    pub fn summary(&self, summary: ChatMessage, replaced: usize) -> SessionMessage {
+        let (covered, replaced) = match self.latest_summary() {
+            // The earlier summary was one of the messages it replaced
+            Some((_, covered)) => (covered, replaced.saturating_sub(1)),
+            None => (0, replaced),
+        };
+        let covered = match replaced {
+            0 => covered,
+            replaced => self.sent_after(covered).nth(replaced - 1).map_or(covered, |(position, _)| position + 1),
+        };
+        SessionMessage { summary_of: Some(covered), ..SessionMessage::new(summary, Utc::now()) }
+    }
+
+    pub fn push(&mut self, message: SessionMessage) {
+        self.updated_at = self.updated_at.max(message.timestamp);
+        if let Some(adapter) = &message.adapter {
+            self.adapter = adapter.clone();
+        }
+        if let Some(model) = &message.model {
+            self.model = model.clone();
+        }
+        if let Some(usage) = message.usage {
+            self.usage += usage;
+        }
+        self.messages.push(message);
+    }
+
+    /// The first question, on one line, for listings.
+    pub fn preview(&self, width: usize) -> String {
+        let question = self.messages.iter()
+            .find(|message| message.message.role == Role::User)
+            .map(|message| message.message.content.split_whitespace().collect::<Vec<_>>().join(" "))
+            .unwrap_or_default();
+        if question.chars().count() > width {
+            format!("{}...", question.chars().take(width.saturating_sub(3)).collect::<String>())
+        } else {
+            question
+        }
+    }
+}
+
+/// Session names become file names, so they are kept to a safe set.
+fn validate_name(name: &str) -> Result<(), Box<dyn Error>> {
+    let valid = !name.is_empty()
+        && name.len() <= 100
+        && !name.starts_with('.')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid session name '{}': use letters, digits, '-', '_' and '.'", name).into())
+    }
+}
+
+pub struct SessionStore {
+    dir: PathBuf,
+}
+
+impl SessionStore {
+    pub fn new(dir: PathBuf) -> Self {
+        SessionStore { dir }
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", name))
+    }
+
+    pub fn load(&self, name: &str) -> Result<Session, Box<dyn Error>> {
+        validate_name(name)?;
+        let path = self.path(name);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Err(format!("No session named '{}'", name).into()),
+            Err(e) => return Err(format!("{}: {}", path.display(), e).into()),
+        };
+        let mut session: Session = serde_json::from_str(&content)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        session.name = name.to_string();
+        Ok(session)
+    }
+
+    /// The named session, or a new empty one if there is none yet.
+    pub fn load_or_new(&self, name: &str) -> Result<Session, Box<dyn Error>> {
+        validate_name(name)?;
+        if self.path(name).exists() {
+            self.load(name)
+        } else {
+            Ok(Session::new(name))
+        }
+    }
+
+    /// Every session, most recently updated first. Unreadable files are
+    /// skipped with a warning.
+    pub fn list(&self) -> Result<Vec<Session>, Box<dyn Error>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(format!("{}: {}", self.dir.display(), e).into()),
+        };
+        let mut sessions = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            let name = match path.file_name().and_then(|name| name.to_str()).and_then(|name| name.strip_suffix(".json")) {
+                Some(name) if validate_name(name).is_ok() => name.to_string(),
+                _ => continue,
+            };
+            match self.load(&name) {
+                Ok(session) => sessions.push(session),
+                Err(e) => eprintln!("Warning: skipping session {}", e),
+            }
+        }
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.updated_at));
+        Ok(sessions)
+    }
+
+    pub fn latest(&self) -> Result<Option<Session>, Box<dyn Error>> {
+        Ok(self.list()?.into_iter().next())
+    }
+
+    /// Starts a session named after the current time. Nothing is written
+    /// until `save` is given its first messages.
+    pub fn start(&self) -> Session {
+        Session { unsaved: true, ..Session::new(Local::now().format("%Y%m%d-%H%M%S").to_string()) }
+    }
+
+    /// Adds messages to `session` on disk and returns it as stored. An unsaved
+    /// session is written as a new file, and its name gets a `-N` suffix if
+    /// taken; the name is picked under the lock, so two runs started in the
+    /// same second keep their own sessions.
+    pub fn save(&self, session: &Session, messages: Vec<SessionMessage>) -> Result<Session, Box<dyn Error>> {
+        if !session.unsaved {
+            return self.append(&session.name, messages);
+        }
+        self.with_lock(|| {
+            let mut name = session.name.clone();
+            let mut n = 1;
+            while self.path(&name).exists() {
+                n += 1;
+                name = format!("{}-{}", session.name, n);
+            }
+            let mut session = Session { name, unsaved: false, ..session.clone() };
+            for message in messages {
+                session.push(message);
+            }
+            self.write(&session)?;
+            Ok(session)
+        })
+    }
+
+    /// Writes a whole new session. Returns `false`, leaving the file alone,
+    /// if a session by that name exists.
+    pub fn create(&self, session: &Session) -> Result<bool, Box<dyn Error>> {
+        validate_name(&session.name)?;
+        self.with_lock(|| {
+            if self.path(&session.name).exists() {
+                return Ok(false);
+            }
+            self.write(session)?;
+            Ok(true)
+        })
+    }
+
+    /// Adds messages to the end of a session, creating it if needed. The file
+    /// is re-read under the lock, so two terminals writing to one session
+    /// both keep their messages.
+    pub fn append(&self, name: &str, messages: Vec<SessionMessage>) -> Result<Session, Box<dyn Error>> {
+        validate_name(name)?;
+        self.with_lock(|| {
+            let mut session = self.load_or_new(name)?;
+            for message in messages {
+                session.push(message);
+            }
+            self.write(&session)?;
+            Ok(session)
+        })
+    }
+
+    pub fn delete(&self, name: &str) -> Result<(), Box<dyn Error>> {
+        validate_name(name)?;
+        self.with_lock(|| match fs::remove_file(self.path(name)) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Err(format!("No session named '{}'", name).into()),
+            Err(e) => Err(e.into()),
+        })
+    }
+
+    pub fn rename(&self, name: &str, new_name: &str) -> Result<(), Box<dyn Error>> {
+        validate_name(name)?;
+        validate_name(new_name)?;
+        self.with_lock(|| {
+            if !self.path(name).exists() {
+                return Err(format!("No session named '{}'", name).into());
+            }
+            if self.path(new_name).exists() {
+                return Err(format!("A session named '{}' already exists", new_name).into());
+            }
+            fs::rename(self.path(name), self.path(new_name))?;
+            Ok(())
+        })
+    }
+
+    /// Runs `f` holding an exclusive lock on the sessions directory.
+    fn with_lock<T>(&self, f: impl FnOnce() -> Result<T, Box<dyn Error>>) -> Result<T, Box<dyn Error>> {
+        fs::create_dir_all(&self.dir)?;
+        let file = OpenOptions::new().create(true).truncate(false).write(true).open(self.dir.join(".lock"))?;
+        let mut lock = RwLock::new(file);
+        let _guard = lock.write()?;
+        f()
+    }
+
+    /// Writes to a temporary file and renames it over the session, so a
+    /// reader never sees a half-written file.
+    fn write(&self, session: &Session) -> Result<(), Box<dyn Error>> {
+        let temp_path = self.dir.join(format!(".{}.json.{}.tmp", session.name, std::process::id()));
+        let mut file = File::create(&temp_path)?;
+        file.write_all(serde_json::to_string_pretty(session)?.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temp_path, self.path(&session.name))?;
+        Ok(())
+    }
+}
+
+/// The session a question belongs to: the latest one with `--continue`, the
+/// named one with `--session`, otherwise a new one.
+pub fn select_session(matches: &ArgMatches, store: &SessionStore) -> Result<Session, Box<dyn Error>> {
+    if matches.get_flag("continue") {
+        return store.latest()?.ok_or_else(|| "There is no session to continue".into());
+    }
+    match matches.get_one::<String>("session") {
+        Some(name) => store.load_or_new(name),
+        None => Ok(store.start()),
+    }
+}
+
+/// Runs `ruskgpt sessions <subcommand>`. Returns the process exit code.
+pub fn handle_sessions_command(matches: &ArgMatches, store: &SessionStore) -> i32 {
+    let result = match matches.subcommand() {
+        Some(("list", _)) => store.list().map(|sessions| {
+            for session in sessions {
+                println!(
+                    "{:<24} {}  {:>3} msgs  {:<20} {}",
+                    session.name,
+                    session.updated_at.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+                    session.messages.len(),
+                    session.model,
+                    session.preview(50)
+                );
+            }
+        }),
+        Some(("show", args)) => store.load(args.get_one::<String>("name").unwrap())
+            .map(|session| print!("{}", session_export::to_markdown(&session))),
+        Some(("export", args)) => store.load(args.get_one::<String>("name").unwrap()).and_then(|session| {
+            let format = session_export::Format::parse(args.get_one::<String>("format").unwrap())?;
+            let text = session_export::export(&session, format)?;
+            match args.get_one::<String>("output") {
+                Some(path) => {
+                    fs::write(path, text).map_err(|e| format!("{}: {}", path, e))?;
+                    eprintln!("Exported session {} to {}", session.name, path);
+                },
+                None => print!("{}", text),
+            }
+            Ok(())
+        }),
+        Some(("import", args)) => {
+            let path = Path::new(args.get_one::<String>("file").unwrap());
+            session_import::import_chatgpt(path, store).map(|summary| {
+                for name in &summary.imported {
+                    println!("{}", name);
+                }
+                eprintln!(
+                    "Imported {} conversations ({} imported before, {} empty)",
+                    summary.imported.len(), summary.existing.len(), summary.empty
+                );
+            })
+        },
+        Some(("delete", args)) => {
+            let name = args.get_one::<String>("name").unwrap();
+            store.delete(name).map(|_| eprintln!("Deleted session {}", name))
+        },
+        Some(("rename", args)) => {
+            let name = args.get_one::<String>("name").unwrap();
+            let new_name = args.get_one::<String>("new-name").unwrap();
+            store.rename(name, new_name).map(|_| eprintln!("Renamed session {} to {}", name, new_name))
+        },
+        _ => unreachable!("clap requires a sessions subcommand"),
+    };
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_store(test: &str) -> SessionStore {
+        let dir = std::env::temp_dir().join(format!("ruskgpt-sessions-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        SessionStore::new(dir)
+    }
+
+    #[test]
+    fn test_append_and_rename() {
+        let store = temp_store("rename");
+        let answer = |text: &str| Answer {
+            text: text.to_string(),
+            reasoning: String::new(),
+            usage: Some(Usage { input_tokens: 10, output_tokens: 4 }),
+        };
+        store.append("rust", vec![
+            SessionMessage::question("What is a trait?"),
+            SessionMessage::answer(answer("An interface."), "openai_adapter", "gpt-4o"),
+        ]).unwrap();
+        let session = store.append("rust", vec![
+            SessionMessage::question("And a lifetime?"),
+            SessionMessage::answer(answer("A scope."), "claude_adapter", "claude-sonnet-4"),
+        ]).unwrap();
+        assert_eq!(session.messages.len(), 4);
+        assert_eq!(session.model, "claude-sonnet-4");
+        assert_eq!(session.usage, Usage { input_tokens: 20, output_tokens: 8 });
+        assert_eq!(store.load("rust").unwrap(), session);
+
+        store.rename("rust", "rust-basics").unwrap();
+        assert_eq!(store.latest().unwrap().unwrap().name, "rust-basics");
+        assert_eq!(store.load("rust").unwrap_err().to_string(), "No session named 'rust'");
+        assert!(store.rename("rust-basics", "../escape").is_err());
+
+        store.delete("rust-basics").unwrap();
+        assert!(store.list().unwrap().is_empty());
+        fs::remove_dir_all(&store.dir).unwrap();
+    }
+
+    #[test]
+    fn test_summaries_replace_the_turns_they_cover() {
+        let mut session = Session::new("long");
+        for i in 0..3 {
+            session.push(SessionMessage::question(format!("question {}", i)));
+            session.push(SessionMessage::new(ChatMessage::assistant(format!("answer {}", i)), Utc::now()));
+        }
+        let summary = session.summary(ChatMessage::system("Summary of questions 0 and 1"), 4);
+        assert_eq!(summary.summary_of, Some(4));
+        session.push(summary);
+        session.push(SessionMessage::question("question 3"));
+        assert_eq!(session.chat_messages(), vec![
+            ChatMessage::system("Summary of questions 0 and 1"),
+            ChatMessage::user("question 2"),
+            ChatMessage::assistant("answer 2"),
+            ChatMessage::user("question 3"),
+        ]);
+
+        // A later summary takes in the earlier one
+        let summary = session.summary(ChatMessage::system("Summary of questions 0 to 2"), 3);
+        assert_eq!(summary.summary_of, Some(6));
+        session.push(summary);
+        assert_eq!(session.chat_messages(), vec![ChatMessage::system("Summary of questions 0 to 2"), ChatMessage::user("question 3")]);
+    }
+
+    #[test]
+    fn test_empty_answers_are_not_sent() {
+        // An imported answer that only called a tool
+        let mut tool_call = SessionMessage::new(ChatMessage::assistant(""), Utc::now());
+        tool_call.tool_calls.push(ToolCall { name: "python".to_string(), input: "1 + 1".to_string(), output: Some("2".to_string()) });
+        let mut session = Session::new("imported");
+        session.push(SessionMessage::question("What is 1 + 1?"));
+        session.push(tool_call);
+        session.push(SessionMessage::new(ChatMessage::assistant("2"), Utc::now()));
+        session.push(SessionMessage::question("And 2 + 2?"));
+        assert_eq!(session.chat_messages(), vec![
+            ChatMessage::user("What is 1 + 1?"),
+            ChatMessage::assistant("2"),
+            ChatMessage::user("And 2 + 2?"),
+        ]);
+
+        // A summary of the first two messages sent covers the tool call too
+        let summary = session.summary(ChatMessage::system("Summary"), 2);
+        assert_eq!(summary.summary_of, Some(3));
+    }
+
+    #[test]
+    fn test_new_sessions_get_their_own_names() {
+        let store = temp_store("new");
+        let first = store.start();
+        let second = Session { name: first.name.clone(), ..store.start() };
+        // Nothing is written before the first question
+        assert!(store.list().unwrap().is_empty());
+
+        let first = store.save(&first, vec![SessionMessage::question("one")]).unwrap();
+        let second = store.save(&second, vec![SessionMessage::question("two")]).unwrap();
+        assert_eq!(second.name, format!("{}-2", first.name));
+        assert!(!first.unsaved && !second.unsaved);
+        assert_eq!(store.load(&first.name).unwrap().preview(10), "one");
+        // Saved sessions are appended to under their name
+        let second = store.save(&second, vec![SessionMessage::question("three")]).unwrap();
+        assert_eq!(store.load(&second.name).unwrap().messages.len(), 2);
+        fs::remove_dir_all(&store.dir).unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_appends_keep_every_message() {
+        let store = std::sync::Arc::new(temp_store("concurrent"));
+        let writers = (0..8).map(|i| {
+            let store = store.clone();
+            std::thread::spawn(move || {
+                store.append("shared", vec![SessionMessage::question(format!("question {}", i))]).unwrap();
+            })
+        }).collect::<Vec<_>>();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        assert_eq!(store.load("shared").unwrap().messages.len(), 8);
+        fs::remove_dir_all(&store.dir).unwrap();
+    }
+}
@@ -0,0 +1,209 @@
+//! A buffered decoder for `text/event-stream` bodies, following the
+//! WHATWG server-sent events parsing rules.
+//!
+//! Bytes are buffered until a full line is available, so events and
+//! multibyte UTF-8 characters may be split across any number of chunks.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SseEvent {
+    /// The `event:` field, or `"message"` when the event had none.
+    pub event: String,
+    /// All `data:` lines of the event, joined with `\n`.
+    pub data: String,
+    /// The last event ID seen on the stream so far.
+    pub id: Option<String>,
+    /// The reconnection time in milliseconds, if the event carried `retry:`.
+    pub retry: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+pub struct SseDecoder {
+    buffer: Vec<u8>,
+    /// The previous chunk ended with CR, so a leading LF belongs to it.
+    skip_lf: bool,
+    started: bool,
+    event: Option<String>,
+    data: String,
+    has_data: bool,
+    last_id: Option<String>,
+    retry: Option<u64>,
+}
+
+impl SseDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds a chunk of the body and returns every event it completed.
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        let mut chunk = chunk;
+        if self.skip_lf && !chunk.is_empty() {
+            if chunk[0] == b'\n' {
+                chunk = &chunk[1..];
+            }
+            self.skip_lf = false;
+        }
+        self.buffer.extend_from_slice(chunk);
+
+        let mut events = Vec::new();
+        let mut start = 0;
+        let mut i = 0;
+        while i < self.buffer.len() {
+            match self.buffer[i] {
+                b'\n' => {
+                    let line = self.buffer[start..i].to_vec();
+                    self.process_line(&line, &mut events);
+                    start = i + 1;
+                },
+                b'\r' => {
+                    let line = self.buffer[start..i].to_vec();
+                    self.process_line(&line, &mut events);
+                    if i + 1 < self.buffer.len() {
+                        if self.buffer[i + 1] == b'\n' {
+                            i += 1;
+                        }
+                    } else {
+                        self.skip_lf = true;
+                    }
+                    start = i + 1;
+                },
+                _ => {},
+            }
+            i += 1;
+        }
+        self.buffer.drain(..start);
+        events
+    }
+
+    /// Flushes whatever is left once the body ends. The spec drops a final
+    /// event that lacks its blank line, but several backends end the body
+    /// without one, so it is dispatched anyway.
+    pub fn finish(&mut self) -> Option<SseEvent> {
+        let mut events = Vec::new();
+        if !self.buffer.is_empty() {
+            let line = std::mem::take(&mut self.buffer);
+            self.process_line(&line, &mut events);
+        }
+        self.dispatch(&mut events);
+        events.pop()
+    }
+
+    fn process_line(&mut self, line: &[u8], events: &mut Vec<SseEvent>) {
+        let mut line = String::from_utf8_lossy(line).into_owned();
+        if !self.started {
+            self.started = true;
+            if let Some(stripped) = line.strip_prefix('\u{feff}') {
+                line = stripped.to_string();
+            }
+        }
+
+        if line.is_empty() {
+            self.dispatch(events);
+            return;
+        }
+        if line.starts_with(':') {
+            // Comment, often used as a keep-alive
+            return;
+        }
+
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line.as_str(), ""),
+        };
+        match field {
+            "event" => self.event = Some(value.to_string()),
+            "data" => {
+                self.data.push_str(value);
+                self.data.push('\n');
+                self.has_data = true;
+            },
+            "id" if !value.contains('\0') => self.last_id = Some(value.to_string()),
+            "retry" if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => {
+                self.retry = value.parse().ok();
+            },
+            _ => {},
+        }
+    }
+
+    fn dispatch(&mut self, events: &mut Vec<SseEvent>) {
+        let event = self.event.take();
+        let retry = self.retry.take();
+        if !self.has_data {
+            self.data.clear();
+            return;
+        }
+        self.has_data = false;
+
+        let mut data = std::mem::take(&mut self.data);
+        data.pop(); // the trailing '\n'
+        events.push(SseEvent {
+            event: event.filter(|event| !event.is_empty()).unwrap_or_else(|| "message".to_string()),
+            data,
+            id: self.last_id.clone(),
+            retry,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_in_chunks(body: &[u8], boundaries: &[usize]) -> Vec<SseEvent> {
+        let mut decoder = SseDecoder::new();
+        let mut events = Vec::new();
+        let mut start = 0;
+        for &end in boundaries.iter().chain(std::iter::once(&body.len())) {
+            events.extend(decoder.feed(&body[start..end]));
+            start = end;
+        }
+        events.extend(decoder.finish());
+        events
+    }
+
+    fn event(event: &str, data: &str, id: Option<&str>, retry: Option<u64>) -> SseEvent {
+        SseEvent { event: event.to_string(), data: data.to_string(), id: id.map(str::to_string), retry }
+    }
+
+    const BODY: &str = "\u{feff}: keep-alive\r\n\
+        event: greeting\r\n\
+        data: 你好，世界\r\n\r\n\
+        id: 7\n\
+        retry: 3000\n\
+        data: first line\n\
+        data:second line\n\
+        data\n\n\
+        event: empty\r\r\
+        data: {\"done\":true}\r\r";
+
+    #[test]
+    fn test_decode_whole_body() {
+        assert_eq!(decode_in_chunks(BODY.as_bytes(), &[]), vec![
+            event("greeting", "你好，世界", None, None),
+            event("message", "first line\nsecond line\n", Some("7"), Some(3000)),
+            event("message", "{\"done\":true}", Some("7"), None),
+        ]);
+    }
+
+    #[test]
+    fn test_decode_any_chunk_boundary() {
+        let body = BODY.as_bytes();
+        let expected = decode_in_chunks(body, &[]);
+
+        // Every single split point, including inside CRLF pairs and
+        // multibyte characters
+        for split in 0..=body.len() {
+            assert_eq!(decode_in_chunks(body, &[split]), expected, "split at {}", split);
+        }
+
+        // One byte at a time
+        let every_byte: Vec<usize> = (1..body.len()).collect();
+        assert_eq!(decode_in_chunks(body, &every_byte), expected);
+    }
+
+    #[test]
+    fn test_unterminated_final_event() {
+        assert_eq!(decode_in_chunks(b"data: [DONE]", &[3]), vec![event("message", "[DONE]", None, None)]);
+        assert_eq!(decode_in_chunks(b"event: ping\n", &[]), vec![]);
+    }
+}